@@ -0,0 +1,152 @@
+#ifndef XR_ETVR_RAW_EYE_DATA_H_
+#define XR_ETVR_RAW_EYE_DATA_H_ 1
+
+/*
+ * XR_ETVR_raw_eye_data
+ *
+ * Vendor extension implemented by the ETVR OpenXR API layer. It exposes everything the eye
+ * tracker sends, before any processing done by the layer.
+ *
+ * The extension is not registered with Khronos, the structure type values below are taken from
+ * a range that no registered extension uses.
+ *
+ * Usage:
+ *   1. Enable "XR_ETVR_raw_eye_data" when creating the instance.
+ *   2. Get the function with xrGetInstanceProcAddr(instance, "xrGetRawEyeDataETVR", ...).
+ *   3. Call it with the XrTime of interest, usually the predicted display time.
+ *
+ * Since spec version 2, chaining an XrGazeClassificationETVR to XrRawEyeDataETVR::next also
+ * returns what the eyes were doing at that sample.
+ *
+ * Since spec version 3, chaining an XrFixationPointETVR also returns where the gaze rays of both
+ * eyes converge. It needs the view poses, so it's only available once the app has called
+ * xrLocateViews.
+ *
+ * Since spec version 4, chaining an XrEyeTorsionETVR also returns the ocular torsion of each eye,
+ * for trackers that measure it.
+ */
+
+#include <openxr/openxr.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define XR_ETVR_raw_eye_data 1
+#define XR_ETVR_raw_eye_data_SPEC_VERSION 4
+#define XR_ETVR_RAW_EYE_DATA_EXTENSION_NAME "XR_ETVR_raw_eye_data"
+
+#define XR_TYPE_RAW_EYE_DATA_GET_INFO_ETVR ((XrStructureType)1999000000)
+#define XR_TYPE_RAW_EYE_DATA_ETVR ((XrStructureType)1999000001)
+#define XR_TYPE_GAZE_CLASSIFICATION_ETVR ((XrStructureType)1999000002)
+#define XR_TYPE_FIXATION_POINT_ETVR ((XrStructureType)1999000003)
+#define XR_TYPE_EYE_TORSION_ETVR ((XrStructureType)1999000004)
+
+typedef XrFlags64 XrRawEyeStateFlagsETVR;
+
+/* pitch and yaw are valid: the eye was tracked in the sample, with enough confidence and open. */
+static const XrRawEyeStateFlagsETVR XR_RAW_EYE_STATE_GAZE_VALID_BIT_ETVR = 0x00000001;
+/* openness, widen and squeeze are valid. */
+static const XrRawEyeStateFlagsETVR XR_RAW_EYE_STATE_OPENNESS_VALID_BIT_ETVR = 0x00000002;
+/* pupilDiameter is valid. */
+static const XrRawEyeStateFlagsETVR XR_RAW_EYE_STATE_PUPIL_DIAMETER_VALID_BIT_ETVR = 0x00000004;
+/* confidence is valid. */
+static const XrRawEyeStateFlagsETVR XR_RAW_EYE_STATE_CONFIDENCE_VALID_BIT_ETVR = 0x00000008;
+
+typedef enum XrRawEyeETVR {
+    XR_RAW_EYE_LEFT_ETVR = 0,
+    XR_RAW_EYE_RIGHT_ETVR = 1,
+    XR_RAW_EYE_COUNT_ETVR = 2,
+    XR_RAW_EYE_MAX_ENUM_ETVR = 0x7FFFFFFF
+} XrRawEyeETVR;
+
+/* Classification of the gaze with velocity threshold, falling back to dispersion. */
+typedef enum XrGazeEventETVR {
+    XR_GAZE_EVENT_FIXATION_ETVR = 0,
+    XR_GAZE_EVENT_SACCADE_ETVR = 1,
+    /* Both eyes are closed. */
+    XR_GAZE_EVENT_BLINK_ETVR = 2,
+    XR_GAZE_EVENT_MAX_ENUM_ETVR = 0x7FFFFFFF
+} XrGazeEventETVR;
+
+typedef struct XrRawEyeDataGetInfoETVR {
+    XrStructureType type;
+    const void* XR_MAY_ALIAS next;
+    /* The sample closest to this time is returned. */
+    XrTime time;
+} XrRawEyeDataGetInfoETVR;
+
+typedef struct XrRawEyeStateETVR {
+    XrRawEyeStateFlagsETVR flags;
+    /* Radians, positive looks up. */
+    float pitch;
+    /* Radians, positive looks left. */
+    float yaw;
+    /* 0 is closed, 1 is fully open. */
+    float openness;
+    /* How much the eye is opened wider than normal, 0 to 1. */
+    float widen;
+    /* How much the eye is squinting, 0 to 1. */
+    float squeeze;
+    /* Millimetres. */
+    float pupilDiameter;
+    /* Tracker confidence, 0 to 1. */
+    float confidence;
+} XrRawEyeStateETVR;
+
+typedef struct XrRawEyeDataETVR {
+    XrStructureType type;
+    void* XR_MAY_ALIAS next;
+    /* When the layer received the sample, 0 if there's no sample. */
+    XrTime sampleTime;
+    /* When the tracker sent the sample, 0 if the tracker doesn't report it. */
+    XrTime senderTime;
+    /* Indexed by XrRawEyeETVR. */
+    XrRawEyeStateETVR eyes[XR_RAW_EYE_COUNT_ETVR];
+} XrRawEyeDataETVR;
+
+/* Spec version 2, may be chained to XrRawEyeDataETVR::next. */
+typedef struct XrGazeClassificationETVR {
+    XrStructureType type;
+    void* XR_MAY_ALIAS next;
+    XrGazeEventETVR event;
+    /* Radians per second, averaged over both eyes, 0 if it's unknown. */
+    float angularVelocity;
+} XrGazeClassificationETVR;
+
+/* Spec version 3, may be chained to XrRawEyeDataETVR::next. Unlike the rest of the extension,
+ * it uses the calibrated gaze. */
+typedef struct XrFixationPointETVR {
+    XrStructureType type;
+    void* XR_MAY_ALIAS next;
+    /* Metres, relative to the point between the eyes in the orientation of the views, with -Z
+     * forward. */
+    XrVector3f position;
+    /* Metres from the point between the eyes. */
+    float distance;
+    /* 0 to 1, 0 if the gaze rays don't converge in front of the user or the view poses aren't
+     * known yet. */
+    float confidence;
+} XrFixationPointETVR;
+
+/* Spec version 4, may be chained to XrRawEyeDataETVR::next. */
+typedef struct XrEyeTorsionETVR {
+    XrStructureType type;
+    void* XR_MAY_ALIAS next;
+    /* Radians about the line of sight, positive is counter-clockwise from the user's point of
+     * view. Indexed by XrRawEyeETVR. */
+    float torsion[XR_RAW_EYE_COUNT_ETVR];
+    /* The tracker sent the torsion of the eye and the eye is tracked. */
+    XrBool32 torsionValid[XR_RAW_EYE_COUNT_ETVR];
+} XrEyeTorsionETVR;
+
+typedef XrResult(XRAPI_PTR* PFN_xrGetRawEyeDataETVR)(
+    XrSession session,
+    const XrRawEyeDataGetInfoETVR* getInfo,
+    XrRawEyeDataETVR* eyeData);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
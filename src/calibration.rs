@@ -0,0 +1,534 @@
+use std::{
+    fmt::Write as _,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use quaternion_core::{QuaternionOps, Vector3};
+
+use crate::{
+    config::Config,
+    pose,
+    source::{Command, EyeGazeData},
+};
+
+/// Number of terms of the calibration polynomial: 1, pitch, yaw, pitch², pitch·yaw, yaw².
+const TERM_COUNT: usize = 6;
+
+/// How many of the polynomial terms a calibration fits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Model {
+    /// Constant offset on pitch and yaw.
+    Offset,
+    /// Offset and gain, pitch and yaw are corrected independently.
+    Gain,
+    /// Pitch and yaw are both corrected from pitch and yaw.
+    Affine,
+    /// Affine plus the quadratic terms.
+    Polynomial,
+}
+
+impl Model {
+    fn parse(name: &str) -> Option<Model> {
+        match name {
+            "offset" => Some(Model::Offset),
+            "gain" => Some(Model::Gain),
+            "affine" => Some(Model::Affine),
+            "polynomial" => Some(Model::Polynomial),
+            _ => None,
+        }
+    }
+
+    /// Terms fitted for the output angle whose own term is `own_term`, 1 for pitch or 2 for yaw.
+    fn terms(self, own_term: usize) -> Vec<usize> {
+        match self {
+            Model::Offset => vec![0],
+            Model::Gain => vec![0, own_term],
+            Model::Affine => vec![0, 1, 2],
+            Model::Polynomial => (0..TERM_COUNT).collect(),
+        }
+    }
+}
+
+/// Maps the measured pitch and yaw of one eye to corrected ones, with a polynomial of up to
+/// second degree for each angle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct EyeCalibration {
+    pitch: [f32; TERM_COUNT],
+    yaw: [f32; TERM_COUNT],
+}
+
+impl EyeCalibration {
+    const IDENTITY: EyeCalibration = EyeCalibration {
+        pitch: [0.0, 1.0, 0.0, 0.0, 0.0, 0.0],
+        yaw: [0.0, 0.0, 1.0, 0.0, 0.0, 0.0],
+    };
+
+    fn apply(&self, pitch: f32, yaw: f32) -> (f32, f32) {
+        let terms = terms(pitch, yaw);
+        let eval = |c: &[f32; TERM_COUNT]| c.iter().zip(terms).map(|(c, t)| c * t).sum();
+        (eval(&self.pitch), eval(&self.yaw))
+    }
+
+    /// Least squares fit of the `model` terms to pairs of target and measured pitch and yaw.
+    fn fit(model: Model, pairs: &[([f32; 2], [f32; 2])]) -> Option<EyeCalibration> {
+        let mut calibration = EyeCalibration::IDENTITY;
+
+        for (angle, own_term) in [(0, 1), (1, 2)] {
+            let coefficients = if angle == 0 {
+                &mut calibration.pitch
+            } else {
+                &mut calibration.yaw
+            };
+
+            let fitted = model.terms(own_term);
+            let rows = pairs.iter().map(|(target, measured)| {
+                let all_terms = terms(measured[0], measured[1]);
+                let row: Vec<f64> = fitted.iter().map(|&t| all_terms[t] as f64).collect();
+                // The offset model keeps the gain at 1 and fits only the remaining error.
+                let target = if model == Model::Offset {
+                    target[angle] - measured[angle]
+                } else {
+                    target[angle]
+                };
+                (row, target as f64)
+            });
+
+            let solution = least_squares(fitted.len(), rows)?;
+
+            if model != Model::Offset {
+                coefficients[own_term] = 0.0;
+            }
+            for (&term, value) in fitted.iter().zip(solution) {
+                coefficients[term] += value as f32;
+            }
+        }
+
+        Some(calibration)
+    }
+}
+
+fn terms(pitch: f32, yaw: f32) -> [f32; TERM_COUNT] {
+    [1.0, pitch, yaw, pitch * pitch, pitch * yaw, yaw * yaw]
+}
+
+/// Solve the normal equations of `rows` of (terms, target), `None` if there isn't enough data
+/// to determine every term.
+fn least_squares(n: usize, rows: impl Iterator<Item = (Vec<f64>, f64)>) -> Option<Vec<f64>> {
+    // Augmented matrix of AᵀA | Aᵀb.
+    let mut m = vec![vec![0.0; n + 1]; n];
+    for (row, target) in rows {
+        for i in 0..n {
+            for j in 0..n {
+                m[i][j] += row[i] * row[j];
+            }
+            m[i][n] += row[i] * target;
+        }
+    }
+
+    // Pivots this small relative to the matrix are rounding errors of a singular matrix.
+    let scale = (0..n).map(|i| m[i][i]).fold(0.0, f64::max);
+    let min_pivot = scale * 1e-10;
+
+    // Gaussian elimination with partial pivoting.
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&a, &b| m[a][col].abs().total_cmp(&m[b][col].abs()))?;
+        if m[pivot][col].abs() <= min_pivot {
+            return None;
+        }
+        m.swap(col, pivot);
+
+        let pivot_row = m[col].clone();
+        for (i, row) in m.iter_mut().enumerate() {
+            if i != col {
+                let factor = row[col] / pivot_row[col];
+                for (value, pivot_value) in row[col..].iter_mut().zip(&pivot_row[col..]) {
+                    *value -= factor * pivot_value;
+                }
+            }
+        }
+    }
+
+    let solution: Vec<f64> = (0..n).map(|i| m[i][n] / m[i][i]).collect();
+    solution.iter().all(|v| v.is_finite()).then_some(solution)
+}
+
+/// The calibration and recenter offset currently applied to the gaze, small enough to publish
+/// to every reader of the gaze at once.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GazeCorrection {
+    eyes: [EyeCalibration; 2],
+    /// Calibrated pitch and yaw of each eye when looking straight ahead, from the last recenter.
+    neutral: [[f32; 2]; 2],
+}
+
+impl GazeCorrection {
+    pub const IDENTITY: GazeCorrection = GazeCorrection {
+        eyes: [EyeCalibration::IDENTITY; 2],
+        neutral: [[0.0; 2]; 2],
+    };
+
+    /// Apply the calibration and recenter offset to both eyes of `sample`.
+    pub fn apply(&self, mut sample: EyeGazeData) -> EyeGazeData {
+        // The calibration models are polynomials of the pitch and yaw.
+        let calibrate = |eye: usize, direction| {
+            let (pitch, yaw) = pose::angles_from_direction(direction);
+            let (pitch, yaw) = self.eyes[eye].apply(pitch, yaw);
+            let [neutral_pitch, neutral_yaw] = self.neutral[eye];
+            pose::direction_from_angles(pitch - neutral_pitch, yaw - neutral_yaw)
+        };
+        sample.l_direction = calibrate(0, sample.l_direction);
+        sample.r_direction = calibrate(1, sample.r_direction);
+        sample
+    }
+}
+
+/// Per-user correction of the measured gaze, with profiles stored as files in a directory.
+///
+/// Calibration is driven by `Command`s: start, then one target per point the user looks at,
+/// then finish with a profile name to fit, save and apply the result. A recenter command takes
+/// the current gaze as straight ahead, on top of the calibration.
+pub struct Calibration {
+    directory: Option<PathBuf>,
+    model: Model,
+    /// Gaze samples received this long before a target are averaged into its measurement.
+    sample_window: Duration,
+    /// Target and measured pitch and yaw for the left and the right eye.
+    pairs: Vec<([f32; 2], [[f32; 2]; 2])>,
+    correction: GazeCorrection,
+}
+
+impl Calibration {
+    pub fn new(config: &Config) -> Calibration {
+        let directory = config
+            .get_str("calibration.directory")
+            .map(PathBuf::from)
+            .or_else(|| config.directory().map(|d| d.join("calibration")));
+
+        let model = config.get_string("calibration.model", "affine");
+        let model = Model::parse(&model).unwrap_or_else(|| {
+            println!("Unknown calibration model {model}, using affine");
+            Model::Affine
+        });
+
+        let mut calibration = Calibration {
+            directory,
+            model,
+            sample_window: Duration::from_secs_f32(config.get("calibration.sample_window", 0.5)),
+            pairs: Vec::new(),
+            correction: GazeCorrection::IDENTITY,
+        };
+
+        if let Some(profile) = config.get_str("calibration.profile") {
+            calibration.load(profile);
+        }
+
+        calibration
+    }
+
+    /// The calibration and recenter offset to apply to the gaze.
+    pub fn correction(&self) -> GazeCorrection {
+        self.correction
+    }
+
+    /// Handle a calibration command, `history` is the uncalibrated gaze, oldest first.
+    pub fn handle_command(&mut self, command: &Command, history: &[EyeGazeData]) {
+        match command {
+            Command::StartCalibration { model } => {
+                if let Some(model) = model {
+                    match Model::parse(model) {
+                        Some(model) => self.model = model,
+                        None => println!("Unknown calibration model {model}"),
+                    }
+                }
+                self.pairs.clear();
+                println!("Calibration started with {:?} model", self.model);
+            }
+            Command::CalibrationTarget { pitch, yaw } => {
+                let Some(measured) = self.measure(history) else {
+                    println!("No gaze data for calibration target");
+                    return;
+                };
+                self.pairs.push(([*pitch, *yaw], measured));
+            }
+            Command::FinishCalibration { profile } => self.finish(profile),
+            Command::LoadCalibration { profile } => self.load(profile),
+            Command::Recenter => {
+                let Some(measured) = self.measure(history) else {
+                    println!("No gaze data to recenter");
+                    return;
+                };
+                let correction = &mut self.correction;
+                for ((neutral, eye), [pitch, yaw]) in correction
+                    .neutral
+                    .iter_mut()
+                    .zip(&correction.eyes)
+                    .zip(measured)
+                {
+                    let (pitch, yaw) = eye.apply(pitch, yaw);
+                    *neutral = [pitch, yaw];
+                }
+                println!("Recentered gaze to {:?}", correction.neutral);
+            }
+            Command::ClearCalibration => {
+                self.correction.eyes = [EyeCalibration::IDENTITY; 2];
+                println!("Calibration cleared");
+            }
+        }
+    }
+
+    /// Pitch and yaw of the average direction of the samples in the last `sample_window` of
+    /// `history`.
+    fn measure(&self, history: &[EyeGazeData]) -> Option<[[f32; 2]; 2]> {
+        let last = history.last()?;
+        let window: Vec<&EyeGazeData> = history
+            .iter()
+            .filter(|s| {
+                last.time
+                    .duration_since(s.time)
+                    .is_ok_and(|d| d <= self.sample_window)
+            })
+            .collect();
+
+        let mean = |f: fn(&EyeGazeData) -> Vector3<f32>| {
+            let sum = window.iter().fold([0.0; 3], |sum, s| sum.add(f(s)));
+            let (pitch, yaw) = pose::angles_from_direction(pose::normalize_direction(sum));
+            [pitch, yaw]
+        };
+        Some([mean(|s| s.l_direction), mean(|s| s.r_direction)])
+    }
+
+    fn finish(&mut self, profile: &str) {
+        let fit = |eye: usize| {
+            let pairs: Vec<([f32; 2], [f32; 2])> = self
+                .pairs
+                .iter()
+                .map(|(target, measured)| (*target, measured[eye]))
+                .collect();
+            EyeCalibration::fit(self.model, &pairs)
+        };
+
+        let (Some(left), Some(right)) = (fit(0), fit(1)) else {
+            println!(
+                "Not enough calibration targets for the {:?} model, got {}",
+                self.model,
+                self.pairs.len()
+            );
+            return;
+        };
+
+        self.correction.eyes = [left, right];
+        println!(
+            "Calibration {profile} fitted from {} targets",
+            self.pairs.len()
+        );
+
+        if let Err(e) = self.save(profile) {
+            println!("Can't save calibration {profile}: {e}");
+        }
+    }
+
+    fn profile_path(&self, profile: &str) -> Option<PathBuf> {
+        // Profile names come from the network, don't let them leave the directory.
+        if profile.is_empty() || profile.contains(['/', '\\']) || profile.starts_with('.') {
+            println!("Invalid calibration profile name {profile}");
+            return None;
+        }
+
+        Some(self.directory.as_deref()?.join(format!("{profile}.conf")))
+    }
+
+    /// Profiles use the config file format, with the coefficients of the pitch and yaw
+    /// polynomials of each eye.
+    fn save(&self, profile: &str) -> std::io::Result<()> {
+        let Some(path) = self.profile_path(profile) else {
+            return Err(std::io::ErrorKind::InvalidInput.into());
+        };
+
+        let mut contents = String::new();
+        for (name, eye) in ["left", "right"].iter().zip(&self.correction.eyes) {
+            let join = |c: &[f32]| c.iter().map(f32::to_string).collect::<Vec<_>>().join(" ");
+            let _ = writeln!(contents, "[{name}]");
+            let _ = writeln!(contents, "pitch = {}", join(&eye.pitch));
+            let _ = writeln!(contents, "yaw = {}", join(&eye.yaw));
+        }
+
+        std::fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))?;
+        std::fs::write(&path, contents)?;
+        println!("Saved calibration to {}", path.display());
+        Ok(())
+    }
+
+    fn load(&mut self, profile: &str) {
+        let Some(path) = self.profile_path(profile) else {
+            return;
+        };
+
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                println!("Can't read calibration {}: {}", path.display(), e);
+                return;
+            }
+        };
+
+        let values = Config::parse(&contents);
+        let coefficients = |key: &str| -> Option<[f32; TERM_COUNT]> {
+            let values: Vec<f32> = values
+                .get_str(key)?
+                .split_whitespace()
+                .map(|v| v.parse().ok())
+                .collect::<Option<_>>()?;
+            values.try_into().ok()
+        };
+        let eye = |name: &str| {
+            Some(EyeCalibration {
+                pitch: coefficients(&format!("{name}.pitch"))?,
+                yaw: coefficients(&format!("{name}.yaw"))?,
+            })
+        };
+
+        match (eye("left"), eye("right")) {
+            (Some(left), Some(right)) => {
+                self.correction.eyes = [left, right];
+                println!("Loaded calibration from {}", path.display());
+            }
+            _ => println!("Invalid calibration {}", path.display()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    /// Measured pitch and yaw on a grid over ±0.4 radians.
+    fn grid() -> Vec<[f32; 2]> {
+        let steps = [-0.4, -0.2, 0.0, 0.2, 0.4];
+        steps
+            .iter()
+            .flat_map(|&pitch| steps.iter().map(move |&yaw| [pitch, yaw]))
+            .collect()
+    }
+
+    /// Fit `model` to targets made from `grid` by `expected`.
+    fn fit(model: Model, expected: EyeCalibration) -> Option<EyeCalibration> {
+        let pairs: Vec<([f32; 2], [f32; 2])> = grid()
+            .into_iter()
+            .map(|measured| {
+                let (pitch, yaw) = expected.apply(measured[0], measured[1]);
+                ([pitch, yaw], measured)
+            })
+            .collect();
+        EyeCalibration::fit(model, &pairs)
+    }
+
+    fn assert_coefficients_eq(actual: EyeCalibration, expected: EyeCalibration) {
+        let close = |a: &[f32], b: &[f32]| a.iter().zip(b).all(|(a, b)| (a - b).abs() < EPSILON);
+        assert!(
+            close(&actual.pitch, &expected.pitch) && close(&actual.yaw, &expected.yaw),
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn fit_recovers_offset() {
+        let expected = EyeCalibration {
+            pitch: [0.05, 1.0, 0.0, 0.0, 0.0, 0.0],
+            yaw: [-0.02, 0.0, 1.0, 0.0, 0.0, 0.0],
+        };
+        assert_coefficients_eq(fit(Model::Offset, expected).unwrap(), expected);
+    }
+
+    #[test]
+    fn fit_recovers_gain() {
+        let expected = EyeCalibration {
+            pitch: [0.01, 1.2, 0.0, 0.0, 0.0, 0.0],
+            yaw: [-0.03, 0.0, 0.9, 0.0, 0.0, 0.0],
+        };
+        assert_coefficients_eq(fit(Model::Gain, expected).unwrap(), expected);
+    }
+
+    #[test]
+    fn fit_recovers_affine() {
+        let expected = EyeCalibration {
+            pitch: [0.01, 1.1, 0.2, 0.0, 0.0, 0.0],
+            yaw: [0.02, -0.1, 0.95, 0.0, 0.0, 0.0],
+        };
+        assert_coefficients_eq(fit(Model::Affine, expected).unwrap(), expected);
+    }
+
+    #[test]
+    fn fit_recovers_polynomial() {
+        let expected = EyeCalibration {
+            pitch: [0.01, 1.1, 0.2, 0.3, -0.1, 0.05],
+            yaw: [0.02, -0.1, 0.95, -0.2, 0.15, 0.1],
+        };
+        assert_coefficients_eq(fit(Model::Polynomial, expected).unwrap(), expected);
+    }
+
+    #[test]
+    fn fit_rejects_underdetermined_input() {
+        let pairs = [([0.1, 0.0], [0.0, 0.0]), ([0.3, 0.2], [0.2, 0.2])];
+        assert_eq!(EyeCalibration::fit(Model::Affine, &pairs), None);
+        assert_eq!(EyeCalibration::fit(Model::Offset, &[]), None);
+    }
+
+    #[test]
+    fn fit_rejects_singular_input() {
+        // Every target at the same pitch leaves the pitch terms undetermined.
+        let pairs: Vec<([f32; 2], [f32; 2])> = [-0.3, -0.1, 0.1, 0.3, 0.5, 0.7, 0.9]
+            .iter()
+            .map(|&yaw| ([0.1, yaw * 1.1], [0.1, yaw]))
+            .collect();
+        assert_eq!(EyeCalibration::fit(Model::Gain, &pairs), None);
+        assert_eq!(EyeCalibration::fit(Model::Polynomial, &pairs), None);
+    }
+
+    #[test]
+    fn recentered_gaze_looks_forward() {
+        let mut calibration = Calibration::new(&Config::parse(""));
+        let sample = EyeGazeData {
+            l_direction: pose::direction_from_angles(-0.1, 0.05),
+            r_direction: pose::direction_from_angles(-0.12, -0.03),
+            ..Default::default()
+        };
+        calibration.handle_command(&Command::Recenter, &[sample]);
+
+        let recentered = calibration.correction().apply(sample);
+        for direction in [recentered.l_direction, recentered.r_direction] {
+            let error = quaternion_core::norm(direction.sub(pose::FORWARD));
+            assert!(error < EPSILON, "{direction:?}");
+        }
+    }
+
+    #[test]
+    fn saved_profile_loads_the_same_coefficients() {
+        let directory =
+            std::env::temp_dir().join(format!("etvr-calibration-test-{}", std::process::id()));
+        let config = Config::parse(&format!(
+            "[calibration]\ndirectory = {}\n",
+            directory.display()
+        ));
+
+        let mut saved = Calibration::new(&config);
+        saved.correction.eyes = [
+            EyeCalibration {
+                pitch: [0.012345678, 1.1, 0.2, 0.3, -0.1, 0.05],
+                yaw: [0.02, -0.1, 0.95, -0.2, 0.15, 1e-7],
+            },
+            EyeCalibration {
+                pitch: [-0.03, 0.9, 0.0, 0.0, 0.0, 0.0],
+                yaw: [0.0, 0.0, 1.0 / 3.0, 0.0, 0.0, 0.0],
+            },
+        ];
+        saved.save("alice").unwrap();
+
+        let mut loaded = Calibration::new(&config);
+        loaded.load("alice");
+        let _ = std::fs::remove_dir_all(&directory);
+        assert_eq!(loaded.correction, saved.correction);
+    }
+}
@@ -0,0 +1,261 @@
+use std::time::{Duration, Instant};
+
+use quaternion_core::{self as quat, QuaternionOps, Vector3};
+
+use crate::{config::Config, pose, source::EyeGazeData};
+
+/// What the eyes are doing, as returned in `XrGazeClassificationETVR::event`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GazeEvent {
+    Fixation = 0,
+    Saccade = 1,
+    Blink = 2,
+}
+
+/// Splits the gaze stream into fixations, saccades and blinks.
+///
+/// Uses the velocity threshold (I-VT) between the last two samples. If they are too far apart in
+/// time for the velocity to mean anything, falls back to the dispersion of the samples in a
+/// short window (I-DT).
+pub struct GazeClassifier {
+    /// Radians per second.
+    velocity_threshold: f32,
+    /// Samples further apart than this use the dispersion instead of the velocity.
+    max_velocity_interval: Duration,
+    /// Radians, sum of the pitch and yaw ranges in the window.
+    dispersion_threshold: f32,
+    dispersion_window: Duration,
+    /// Openness below which an eye is considered closed.
+    blink_openness: f32,
+}
+
+impl GazeClassifier {
+    pub fn new(config: &Config) -> GazeClassifier {
+        GazeClassifier {
+            velocity_threshold: config
+                .get::<f32>("classify.velocity_threshold", 100.0)
+                .to_radians(),
+            max_velocity_interval: Duration::from_secs_f32(
+                config.get("classify.max_velocity_interval", 0.05),
+            ),
+            dispersion_threshold: config
+                .get::<f32>("classify.dispersion_threshold", 1.5)
+                .to_radians(),
+            dispersion_window: Duration::from_secs_f32(
+                config.get("classify.dispersion_window", 0.1),
+            ),
+            blink_openness: config.get("classify.blink_openness", 0.2),
+        }
+    }
+
+    /// Classify the last sample of `history`, which is ordered oldest first. Also returns the
+    /// angular speed of the gaze in radians per second, 0 if it's unknown.
+    pub fn classify(&self, history: &[EyeGazeData]) -> (GazeEvent, f32) {
+        let Some(last) = history.last() else {
+            return (GazeEvent::Fixation, 0.0);
+        };
+
+        if last.l_openness < self.blink_openness && last.r_openness < self.blink_openness {
+            return (GazeEvent::Blink, 0.0);
+        }
+
+        if let [.., previous, _] = history {
+            let dt = last.time.duration_since(previous.time).unwrap_or_default();
+            if !dt.is_zero() && dt <= self.max_velocity_interval {
+                let velocity = angular_distance(previous, last) / dt.as_secs_f32();
+                let event = if velocity > self.velocity_threshold {
+                    GazeEvent::Saccade
+                } else {
+                    GazeEvent::Fixation
+                };
+                return (event, velocity);
+            }
+        }
+
+        let window = history.iter().rev().take_while(|s| {
+            last.time
+                .duration_since(s.time)
+                .is_ok_and(|d| d <= self.dispersion_window)
+        });
+        let event = if dispersion(window) > self.dispersion_threshold {
+            GazeEvent::Saccade
+        } else {
+            GazeEvent::Fixation
+        };
+        (event, 0.0)
+    }
+}
+
+/// Angle between two samples, averaged over both eyes.
+fn angular_distance(a: &EyeGazeData, b: &EyeGazeData) -> f32 {
+    // Unlike acos of the dot product, precise for the tiny angles between samples.
+    let angle = |u: Vector3<f32>, v| quat::norm(quat::cross(u, v)).atan2(quat::dot(u, v));
+    (angle(a.l_direction, b.l_direction) + angle(a.r_direction, b.r_direction)) / 2.0
+}
+
+/// Sum of the pitch and yaw ranges, averaged over both eyes.
+fn dispersion<'a>(samples: impl Iterator<Item = &'a EyeGazeData>) -> f32 {
+    let mut min = [f32::INFINITY; 4];
+    let mut max = [f32::NEG_INFINITY; 4];
+    for s in samples {
+        let (l_pitch, l_yaw) = pose::angles_from_direction(s.l_direction);
+        let (r_pitch, r_yaw) = pose::angles_from_direction(s.r_direction);
+        let angles = [l_pitch, l_yaw, r_pitch, r_yaw];
+        for i in 0..4 {
+            min[i] = min[i].min(angles[i]);
+            max[i] = max[i].max(angles[i]);
+        }
+    }
+
+    if min[0] > max[0] {
+        return 0.0;
+    }
+    let range = |i: usize| max[i] - min[i];
+    (range(0) + range(1) + range(2) + range(3)) / 2.0
+}
+
+/// What the pupil offset in `xrLocateViews` does while the eyes aren't fixating.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SaccadeViewMode {
+    /// Follow the gaze.
+    Follow,
+    /// Keep the gaze of the last fixation.
+    Hold,
+    /// Move towards the current gaze with a time constant.
+    Ease,
+}
+
+/// Holds or eases the gaze used for the pupil offset during saccades and blinks, so the views
+/// don't jump around.
+pub struct ViewGaze {
+    mode: SaccadeViewMode,
+    ease_time: f32,
+    /// Left and right gaze direction of the last call.
+    directions: Option<[Vector3<f32>; 2]>,
+    last_update: Instant,
+}
+
+impl ViewGaze {
+    pub fn new(config: &Config) -> ViewGaze {
+        let mode = match config.get_str("classify.saccade_view_mode") {
+            Some("follow") | None => SaccadeViewMode::Follow,
+            Some("hold") => SaccadeViewMode::Hold,
+            Some("ease") => SaccadeViewMode::Ease,
+            Some(mode) => {
+                println!("Unknown saccade view mode {mode}, following the gaze");
+                SaccadeViewMode::Follow
+            }
+        };
+
+        ViewGaze {
+            mode,
+            ease_time: config.get("classify.ease_time", 0.05),
+            directions: None,
+            last_update: Instant::now(),
+        }
+    }
+
+    /// Directions to use for the pupil offset given the current event and gaze.
+    pub fn update(&mut self, event: GazeEvent, directions: [Vector3<f32>; 2]) -> [Vector3<f32>; 2] {
+        let now = Instant::now();
+        let dt = now.duration_since(self.last_update).as_secs_f32();
+        self.last_update = now;
+
+        let directions = match (self.directions, event, self.mode) {
+            (Some(previous), GazeEvent::Saccade | GazeEvent::Blink, SaccadeViewMode::Hold) => {
+                previous
+            }
+            (Some(previous), GazeEvent::Saccade | GazeEvent::Blink, SaccadeViewMode::Ease) => {
+                let alpha = 1.0 - (-dt / self.ease_time.max(f32::EPSILON)).exp();
+                let ease = |previous: Vector3<f32>, current: Vector3<f32>| {
+                    pose::normalize_direction(previous.add(current.sub(previous).scale(alpha)))
+                };
+                [
+                    ease(previous[0], directions[0]),
+                    ease(previous[1], directions[1]),
+                ]
+            }
+            _ => directions,
+        };
+
+        self.directions = Some(directions);
+        directions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::*;
+
+    fn classifier() -> GazeClassifier {
+        GazeClassifier::new(&Config::parse(""))
+    }
+
+    /// A sample looking `yaw` degrees to the left with both eyes, `ms` after the epoch.
+    fn sample(ms: u64, yaw: f32) -> EyeGazeData {
+        let direction = pose::direction_from_angles(0.0, yaw.to_radians());
+        EyeGazeData {
+            l_direction: direction,
+            r_direction: direction,
+            time: SystemTime::UNIX_EPOCH + Duration::from_millis(ms),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn velocity_threshold_separates_saccades() {
+        // The default threshold is 100°/s, samples are 8 ms apart.
+        let (event, velocity) = classifier().classify(&[sample(1000, 0.0), sample(1008, 0.5)]);
+        assert_eq!(event, GazeEvent::Fixation);
+        assert!((velocity.to_degrees() - 62.5).abs() < 0.1, "{velocity}");
+
+        let (event, velocity) = classifier().classify(&[sample(1000, 0.0), sample(1008, 1.0)]);
+        assert_eq!(event, GazeEvent::Saccade);
+        assert!((velocity.to_degrees() - 125.0).abs() < 0.1, "{velocity}");
+    }
+
+    #[test]
+    fn sparse_samples_use_dispersion() {
+        // 80 ms apart is too far for the velocity, both fall in the 100 ms dispersion window
+        // with its 1.5° threshold.
+        let (event, velocity) = classifier().classify(&[sample(1000, 0.0), sample(1080, 1.0)]);
+        assert_eq!((event, velocity), (GazeEvent::Fixation, 0.0));
+
+        let (event, velocity) = classifier().classify(&[sample(1000, 0.0), sample(1080, 2.0)]);
+        assert_eq!((event, velocity), (GazeEvent::Saccade, 0.0));
+
+        // Samples older than the window don't count.
+        let history = [sample(900, 5.0), sample(1000, 0.0), sample(1080, 1.0)];
+        assert_eq!(classifier().classify(&history).0, GazeEvent::Fixation);
+    }
+
+    #[test]
+    fn closed_eyes_are_blinks() {
+        let closed = EyeGazeData {
+            l_openness: 0.1,
+            r_openness: 0.05,
+            ..sample(1008, 10.0)
+        };
+        assert_eq!(
+            classifier().classify(&[sample(1000, 0.0), closed]),
+            (GazeEvent::Blink, 0.0)
+        );
+
+        // One closed eye is a wink, the gaze of the open one still counts.
+        let wink = EyeGazeData {
+            r_openness: 1.0,
+            ..closed
+        };
+        assert_eq!(
+            classifier().classify(&[sample(1000, 0.0), wink]).0,
+            GazeEvent::Saccade
+        );
+    }
+
+    #[test]
+    fn empty_history_is_a_fixation() {
+        assert_eq!(classifier().classify(&[]), (GazeEvent::Fixation, 0.0));
+    }
+}
@@ -0,0 +1,113 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Layer settings loaded from a plain `key = value` file.
+///
+/// Keys inside a `[section]` are stored as `section.key`. Lines starting with `#` are comments.
+/// The file is looked up at `$ETVR_LAYER_CONFIG`, falling back to
+/// `/sdcard/Android/data/<package>/files/etvr-openxr-layer.conf` for the current process.
+#[derive(Debug, Default, Clone)]
+pub struct Config {
+    values: HashMap<String, String>,
+    /// Directory the config was loaded from, or would have been.
+    directory: Option<PathBuf>,
+}
+
+impl Config {
+    pub fn load() -> Config {
+        let Some(path) = config_path() else {
+            return Config::default();
+        };
+
+        let config = match std::fs::read_to_string(&path) {
+            Ok(contents) => {
+                println!("Loaded config from {}", path.display());
+                Config::parse(&contents)
+            }
+            Err(e) => {
+                println!("Using default config, can't read {}: {}", path.display(), e);
+                Config::default()
+            }
+        };
+
+        Config {
+            directory: path.parent().map(PathBuf::from),
+            ..config
+        }
+    }
+
+    pub fn parse(contents: &str) -> Config {
+        let mut values = HashMap::new();
+        let mut section = String::new();
+
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = name.trim().to_string();
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                println!("Ignoring malformed config line: {line}");
+                continue;
+            };
+
+            let key = if section.is_empty() {
+                key.trim().to_string()
+            } else {
+                format!("{}.{}", section, key.trim())
+            };
+            values.insert(key, value.trim().to_string());
+        }
+
+        Config {
+            values,
+            directory: None,
+        }
+    }
+
+    pub fn directory(&self) -> Option<&Path> {
+        self.directory.as_deref()
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|v| v.as_str())
+    }
+
+    /// Parse the value of `key`, falling back to `default` if it's missing or invalid.
+    pub fn get<T: FromStr>(&self, key: &str, default: T) -> T {
+        match self.get_str(key) {
+            Some(value) => value.parse().unwrap_or_else(|_| {
+                println!("Invalid value for {key}: {value}");
+                default
+            }),
+            None => default,
+        }
+    }
+
+    pub fn get_string(&self, key: &str, default: &str) -> String {
+        self.get_str(key).unwrap_or(default).to_string()
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("ETVR_LAYER_CONFIG") {
+        return Some(path.into());
+    }
+
+    // On Android the process name is the package name.
+    let cmdline = std::fs::read("/proc/self/cmdline").ok()?;
+    let package = cmdline.split(|&b| b == 0).next()?;
+    let package = std::str::from_utf8(package).ok()?;
+
+    Some(PathBuf::from(format!(
+        "/sdcard/Android/data/{package}/files/etvr-openxr-layer.conf"
+    )))
+}
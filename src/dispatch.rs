@@ -1,14 +1,31 @@
 use std::ffi::c_char;
 use std::ffi::CStr;
+use std::ffi::CString;
+use std::ptr;
 
-use crate::layer::INSTANCE;
+use crate::layer::{
+    extension_name, is_advertised_extension, is_layer_only_extension, next_extensions, OpenXRLayer,
+    INSTANCE,
+};
+use crate::raw_eye_data;
+use crate::sys;
 
 use openxr_sys::ActionSpaceCreateInfo;
+use openxr_sys::ActionStateBoolean;
 use openxr_sys::ActionStateGetInfo;
 use openxr_sys::ActionStatePose;
+use openxr_sys::ActionStateVector2f;
+use openxr_sys::EventDataBuffer;
 use openxr_sys::ExtensionProperties;
+use openxr_sys::FacialExpressionsHTC;
+use openxr_sys::FacialTrackerCreateInfoHTC;
+use openxr_sys::FacialTrackerHTC;
+use openxr_sys::FoveationEyeTrackedStateMETA;
+use openxr_sys::FrameState;
+use openxr_sys::FrameWaitInfo;
 use openxr_sys::InteractionProfileSuggestedBinding;
 use openxr_sys::Session;
+use openxr_sys::SessionCreateInfo;
 use openxr_sys::Space;
 use openxr_sys::SpaceLocation;
 use openxr_sys::SystemId;
@@ -21,6 +38,17 @@ use openxr_sys::{pfn, Instance, Result};
 
 use openxr_sys::{loader::ApiLayerCreateInfo, InstanceCreateInfo};
 
+/// The layer, for the entry points that only read its setup and synchronize the rest.
+unsafe fn layer() -> &'static OpenXRLayer {
+    &*ptr::addr_of!(INSTANCE)
+}
+
+/// The layer, for the entry points that set it up. Apps call them before using what they set
+/// up, like creating a session before running its frame loop.
+unsafe fn layer_mut() -> &'static mut OpenXRLayer {
+    &mut *ptr::addr_of_mut!(INSTANCE)
+}
+
 pub unsafe extern "system" fn xr_create_api_layer_instance(
     instance_create_info_ptr: *const InstanceCreateInfo,
     api_layer_info_ptr: *const ApiLayerCreateInfo,
@@ -31,13 +59,39 @@ pub unsafe extern "system" fn xr_create_api_layer_instance(
     // Call the chain to create the instance.
     let mut chain_instance_create_info = *instance_create_info_ptr;
 
-    // Hide our extension from the list assuming it's in the beginning.
+    let api_layer_info = *api_layer_info_ptr;
+    let runtime_extensions: Vec<String> =
+        match next_enumerate_instance_extension_properties(&api_layer_info)
+            .map(|enumerate| next_extensions(enumerate, ptr::null()))
+        {
+            Some(Ok(extensions)) => extensions.iter().map(extension_name).collect(),
+            Some(Err(result)) => {
+                println!("Can't enumerate the runtime extensions: {result:?}");
+                Vec::new()
+            }
+            None => Vec::new(),
+        };
+
+    // Hide the extensions only we implement from the list.
     // This is to avoid an `ERROR_EXTENSION_NOT_PRESENT` error from the runtime.
-    chain_instance_create_info.enabled_extension_count -= 1;
-    chain_instance_create_info.enabled_extension_names =
-        chain_instance_create_info.enabled_extension_names.add(1);
+    let enabled_extension_names = std::slice::from_raw_parts(
+        (*instance_create_info_ptr).enabled_extension_names,
+        (*instance_create_info_ptr)
+            .enabled_extension_count
+            .try_into()
+            .unwrap(),
+    );
+    let chain_extension_names: Vec<*const c_char> = enabled_extension_names
+        .iter()
+        .copied()
+        .filter(|&name| {
+            !is_layer_only_extension(&CStr::from_ptr(name).to_string_lossy(), &runtime_extensions)
+        })
+        .collect();
+    chain_instance_create_info.enabled_extension_count =
+        chain_extension_names.len().try_into().unwrap();
+    chain_instance_create_info.enabled_extension_names = chain_extension_names.as_ptr();
 
-    let api_layer_info = *api_layer_info_ptr;
     let mut chain_api_layer_info = api_layer_info;
     chain_api_layer_info.next_info = (*api_layer_info.next_info).next;
     let result = ((*api_layer_info.next_info).next_create_api_layer_instance)(
@@ -50,9 +104,26 @@ pub unsafe extern "system" fn xr_create_api_layer_instance(
 
     if result == Result::SUCCESS {
         // Create our layer.
-        INSTANCE.get_instance_proc_addr =
+        let layer = layer_mut();
+        layer.get_instance_proc_addr =
             Some((*api_layer_info.next_info).next_get_instance_proc_addr);
-        INSTANCE.instance = Some(*instance);
+        layer.instance = Some(*instance);
+        layer.enabled_extensions = enabled_extension_names
+            .iter()
+            .map(|&name| CStr::from_ptr(name).to_string_lossy().to_string())
+            .collect();
+        layer.native_extensions = layer
+            .enabled_extensions
+            .iter()
+            .filter(|name| {
+                is_advertised_extension(name) && !is_layer_only_extension(name, &runtime_extensions)
+            })
+            .cloned()
+            .collect();
+        println!(
+            "Extensions also handled by the runtime: {:?}",
+            layer.native_extensions
+        );
     }
 
     println!("<-- xr_create_api_layer_instance");
@@ -60,6 +131,42 @@ pub unsafe extern "system" fn xr_create_api_layer_instance(
     result
 }
 
+/// `xrEnumerateInstanceExtensionProperties` of the next layer or the runtime.
+unsafe fn next_enumerate_instance_extension_properties(
+    api_layer_info: &ApiLayerCreateInfo,
+) -> Option<pfn::EnumerateInstanceExtensionProperties> {
+    let mut function = None;
+    let name = CString::new("xrEnumerateInstanceExtensionProperties").unwrap();
+    let result = ((*api_layer_info.next_info).next_get_instance_proc_addr)(
+        Instance::NULL,
+        name.as_ptr(),
+        &mut function,
+    );
+    if result != Result::SUCCESS {
+        return None;
+    }
+    function.map(|function| {
+        std::mem::transmute::<pfn::VoidFunction, pfn::EnumerateInstanceExtensionProperties>(
+            function,
+        )
+    })
+}
+
+/// Extension of a function implemented by the layer.
+fn layer_function_extension(api_name: &str) -> Option<&'static str> {
+    match api_name {
+        "xrCreateFaceTracker2FB" | "xrDestroyFaceTracker2FB" | "xrGetFaceExpressionWeights2FB" => {
+            Some("XR_FB_face_tracking2")
+        }
+        "xrCreateFacialTrackerHTC" | "xrDestroyFacialTrackerHTC" | "xrGetFacialExpressionsHTC" => {
+            Some("XR_HTC_facial_tracking")
+        }
+        "xrGetFoveationEyeTrackedStateMETA" => Some("XR_META_foveation_eye_tracked"),
+        "xrGetRawEyeDataETVR" => Some(raw_eye_data::EXTENSION_NAME),
+        _ => None,
+    }
+}
+
 pub unsafe extern "system" fn xr_get_instance_proc_addr(
     instance: Instance,
     name_ptr: *const c_char,
@@ -80,10 +187,11 @@ pub unsafe extern "system" fn xr_get_instance_proc_addr(
         CStr::from_ptr(name_ptr).to_str().unwrap()
     );
 
-    let result = INSTANCE.get_instance_proc_addr.unwrap()(instance, name_ptr, function);
+    let layer = layer_mut();
+    let result = layer.get_instance_proc_addr.unwrap()(instance, name_ptr, function);
 
     if api_name == "xrEnumerateInstanceExtensionProperties" {
-        INSTANCE.enumerate_instance_extensions_properties = Some(std::mem::transmute::<
+        layer.enumerate_instance_extensions_properties = Some(std::mem::transmute::<
             pfn::VoidFunction,
             pfn::EnumerateInstanceExtensionProperties,
         >((*function).unwrap()));
@@ -94,7 +202,7 @@ pub unsafe extern "system" fn xr_get_instance_proc_addr(
     }
 
     if api_name == "xrGetSystemProperties" {
-        INSTANCE.get_system_properties = Some(std::mem::transmute::<
+        layer.get_system_properties = Some(std::mem::transmute::<
             pfn::VoidFunction,
             pfn::GetSystemProperties,
         >((*function).unwrap()));
@@ -105,7 +213,7 @@ pub unsafe extern "system" fn xr_get_instance_proc_addr(
     }
 
     if api_name == "xrSuggestInteractionProfileBindings" {
-        INSTANCE.suggest_interaction_profile_bindings = Some(std::mem::transmute::<
+        layer.suggest_interaction_profile_bindings = Some(std::mem::transmute::<
             pfn::VoidFunction,
             pfn::SuggestInteractionProfileBindings,
         >((*function).unwrap()));
@@ -116,7 +224,7 @@ pub unsafe extern "system" fn xr_get_instance_proc_addr(
     }
 
     if api_name == "xrCreateActionSpace" {
-        INSTANCE.create_action_space = Some(std::mem::transmute::<
+        layer.create_action_space = Some(std::mem::transmute::<
             pfn::VoidFunction,
             pfn::CreateActionSpace,
         >((*function).unwrap()));
@@ -127,7 +235,7 @@ pub unsafe extern "system" fn xr_get_instance_proc_addr(
     }
 
     if api_name == "xrGetActionStatePose" {
-        INSTANCE.get_action_state_pose = Some(std::mem::transmute::<
+        layer.get_action_state_pose = Some(std::mem::transmute::<
             pfn::VoidFunction,
             pfn::GetActionStatePose,
         >((*function).unwrap()));
@@ -137,8 +245,30 @@ pub unsafe extern "system" fn xr_get_instance_proc_addr(
         >(xr_get_action_state_pose));
     }
 
+    if api_name == "xrGetActionStateVector2f" {
+        layer.get_action_state_vector2f = Some(std::mem::transmute::<
+            pfn::VoidFunction,
+            pfn::GetActionStateVector2f,
+        >((*function).unwrap()));
+        *function = Some(std::mem::transmute::<
+            pfn::GetActionStateVector2f,
+            pfn::VoidFunction,
+        >(xr_get_action_state_vector2f));
+    }
+
+    if api_name == "xrGetActionStateBoolean" {
+        layer.get_action_state_boolean = Some(std::mem::transmute::<
+            pfn::VoidFunction,
+            pfn::GetActionStateBoolean,
+        >((*function).unwrap()));
+        *function = Some(std::mem::transmute::<
+            pfn::GetActionStateBoolean,
+            pfn::VoidFunction,
+        >(xr_get_action_state_boolean));
+    }
+
     if api_name == "xrLocateSpace" {
-        INSTANCE.locate_space = Some(std::mem::transmute::<pfn::VoidFunction, pfn::LocateSpace>(
+        layer.locate_space = Some(std::mem::transmute::<pfn::VoidFunction, pfn::LocateSpace>(
             (*function).unwrap(),
         ));
         *function = Some(std::mem::transmute::<pfn::LocateSpace, pfn::VoidFunction>(
@@ -147,7 +277,7 @@ pub unsafe extern "system" fn xr_get_instance_proc_addr(
     }
 
     if api_name == "xrLocateViews" {
-        INSTANCE.locate_views = Some(std::mem::transmute::<pfn::VoidFunction, pfn::LocateViews>(
+        layer.locate_views = Some(std::mem::transmute::<pfn::VoidFunction, pfn::LocateViews>(
             (*function).unwrap(),
         ));
         *function = Some(std::mem::transmute::<pfn::LocateViews, pfn::VoidFunction>(
@@ -155,10 +285,134 @@ pub unsafe extern "system" fn xr_get_instance_proc_addr(
         ));
     }
 
-    if api_name == "xrPathToString" {
-        INSTANCE.path_to_string = Some(
-            std::mem::transmute::<pfn::VoidFunction, pfn::PathToString>((*function).unwrap()),
+    if api_name == "xrWaitFrame" {
+        layer.wait_frame = Some(std::mem::transmute::<pfn::VoidFunction, pfn::WaitFrame>(
+            (*function).unwrap(),
+        ));
+        *function = Some(std::mem::transmute::<pfn::WaitFrame, pfn::VoidFunction>(
+            xr_wait_frame,
+        ));
+    }
+
+    if api_name == "xrCreateSession" {
+        layer.create_session = Some(
+            std::mem::transmute::<pfn::VoidFunction, pfn::CreateSession>((*function).unwrap()),
+        );
+        *function =
+            Some(std::mem::transmute::<pfn::CreateSession, pfn::VoidFunction>(xr_create_session));
+    }
+
+    if api_name == "xrDestroySession" {
+        layer.destroy_session = Some(
+            std::mem::transmute::<pfn::VoidFunction, pfn::DestroySession>((*function).unwrap()),
         );
+        *function =
+            Some(std::mem::transmute::<pfn::DestroySession, pfn::VoidFunction>(xr_destroy_session));
+    }
+
+    if api_name == "xrPollEvent" {
+        layer.poll_event = Some(std::mem::transmute::<pfn::VoidFunction, pfn::PollEvent>(
+            (*function).unwrap(),
+        ));
+        *function = Some(std::mem::transmute::<pfn::PollEvent, pfn::VoidFunction>(
+            xr_poll_event,
+        ));
+    }
+
+    if api_name == "xrPathToString" {
+        layer.path_to_string = Some(std::mem::transmute::<pfn::VoidFunction, pfn::PathToString>(
+            (*function).unwrap(),
+        ));
+    }
+
+    if api_name == "xrStringToPath" {
+        layer.string_to_path = Some(std::mem::transmute::<pfn::VoidFunction, pfn::StringToPath>(
+            (*function).unwrap(),
+        ));
+    }
+
+    if api_name == "xrCreateReferenceSpace" {
+        layer.create_reference_space = Some(std::mem::transmute::<
+            pfn::VoidFunction,
+            pfn::CreateReferenceSpace,
+        >((*function).unwrap()));
+    }
+
+    // Functions implemented by the layer, only available if the app enabled their extension.
+    // Face tracking is left to a runtime that has it, the eye tracked foveation state is always
+    // ours.
+    if let Some(extension) = layer_function_extension(&api_name) {
+        if !layer.is_extension_enabled(extension) {
+            *function = None;
+            return Result::ERROR_FUNCTION_UNSUPPORTED;
+        }
+        if extension == "XR_FB_face_tracking2" && layer.is_native_extension(extension) {
+            return result;
+        }
+    }
+
+    if api_name == "xrCreateFaceTracker2FB" {
+        *function = Some(std::mem::transmute::<
+            sys::CreateFaceTracker2FB,
+            pfn::VoidFunction,
+        >(xr_create_face_tracker2_fb));
+        return Result::SUCCESS;
+    }
+
+    if api_name == "xrDestroyFaceTracker2FB" {
+        *function = Some(std::mem::transmute::<
+            sys::DestroyFaceTracker2FB,
+            pfn::VoidFunction,
+        >(xr_destroy_face_tracker2_fb));
+        return Result::SUCCESS;
+    }
+
+    if api_name == "xrGetFaceExpressionWeights2FB" {
+        *function = Some(std::mem::transmute::<
+            sys::GetFaceExpressionWeights2FB,
+            pfn::VoidFunction,
+        >(xr_get_face_expression_weights2_fb));
+        return Result::SUCCESS;
+    }
+
+    if api_name == "xrCreateFacialTrackerHTC" {
+        *function = Some(std::mem::transmute::<
+            pfn::CreateFacialTrackerHTC,
+            pfn::VoidFunction,
+        >(xr_create_facial_tracker_htc));
+        return Result::SUCCESS;
+    }
+
+    if api_name == "xrDestroyFacialTrackerHTC" {
+        *function = Some(std::mem::transmute::<
+            pfn::DestroyFacialTrackerHTC,
+            pfn::VoidFunction,
+        >(xr_destroy_facial_tracker_htc));
+        return Result::SUCCESS;
+    }
+
+    if api_name == "xrGetFacialExpressionsHTC" {
+        *function = Some(std::mem::transmute::<
+            pfn::GetFacialExpressionsHTC,
+            pfn::VoidFunction,
+        >(xr_get_facial_expressions_htc));
+        return Result::SUCCESS;
+    }
+
+    if api_name == "xrGetFoveationEyeTrackedStateMETA" {
+        *function = Some(std::mem::transmute::<
+            pfn::GetFoveationEyeTrackedStateMETA,
+            pfn::VoidFunction,
+        >(xr_get_foveation_eye_tracked_state_meta));
+        return Result::SUCCESS;
+    }
+
+    if api_name == "xrGetRawEyeDataETVR" {
+        *function = Some(std::mem::transmute::<
+            raw_eye_data::GetRawEyeDataETVR,
+            pfn::VoidFunction,
+        >(xr_get_raw_eye_data_etvr));
+        return Result::SUCCESS;
     }
 
     result
@@ -170,7 +424,7 @@ unsafe extern "system" fn xr_enumerate_instance_extension_properties(
     property_count_output: *mut u32,
     properties: *mut ExtensionProperties,
 ) -> Result {
-    INSTANCE.enumerate_instance_extension_properties(
+    layer().enumerate_instance_extension_properties(
         layer_name,
         property_capacity_input,
         property_count_output,
@@ -183,14 +437,14 @@ unsafe extern "system" fn xr_get_system_properties(
     system_id: SystemId,
     properties: *mut SystemProperties,
 ) -> Result {
-    INSTANCE.get_system_properties(instance, system_id, properties)
+    layer().get_system_properties(instance, system_id, properties)
 }
 
 unsafe extern "system" fn xr_suggest_interaction_profile_bindings(
     instance: Instance,
     suggested_bindings: *const InteractionProfileSuggestedBinding,
 ) -> Result {
-    INSTANCE.suggest_interaction_profile_bindings(instance, suggested_bindings)
+    layer_mut().suggest_interaction_profile_bindings(instance, suggested_bindings)
 }
 
 unsafe extern "system" fn xr_create_action_space(
@@ -198,7 +452,23 @@ unsafe extern "system" fn xr_create_action_space(
     create_info: *const ActionSpaceCreateInfo,
     space: *mut Space,
 ) -> Result {
-    INSTANCE.create_action_space(session, create_info, space)
+    layer_mut().create_action_space(session, create_info, space)
+}
+
+unsafe extern "system" fn xr_get_action_state_boolean(
+    session: Session,
+    get_info: *const ActionStateGetInfo,
+    state: *mut ActionStateBoolean,
+) -> Result {
+    layer().get_action_state_boolean(session, get_info, state)
+}
+
+unsafe extern "system" fn xr_get_action_state_vector2f(
+    session: Session,
+    get_info: *const ActionStateGetInfo,
+    state: *mut ActionStateVector2f,
+) -> Result {
+    layer().get_action_state_vector2f(session, get_info, state)
 }
 
 unsafe extern "system" fn xr_get_action_state_pose(
@@ -206,7 +476,7 @@ unsafe extern "system" fn xr_get_action_state_pose(
     get_info: *const ActionStateGetInfo,
     state: *mut ActionStatePose,
 ) -> Result {
-    INSTANCE.get_action_state_pose(session, get_info, state)
+    layer().get_action_state_pose(session, get_info, state)
 }
 
 unsafe extern "system" fn xr_locate_space(
@@ -215,7 +485,7 @@ unsafe extern "system" fn xr_locate_space(
     time: Time,
     location: *mut SpaceLocation,
 ) -> Result {
-    INSTANCE.locate_space(space, base_space, time, location)
+    layer().locate_space(space, base_space, time, location)
 }
 
 unsafe extern "system" fn xr_locate_views(
@@ -226,7 +496,7 @@ unsafe extern "system" fn xr_locate_views(
     view_count_output: *mut u32,
     views: *mut View,
 ) -> Result {
-    INSTANCE.locate_views(
+    layer().locate_views(
         session,
         view_locate_info,
         view_state,
@@ -235,3 +505,86 @@ unsafe extern "system" fn xr_locate_views(
         views,
     )
 }
+
+unsafe extern "system" fn xr_wait_frame(
+    session: Session,
+    frame_wait_info: *const FrameWaitInfo,
+    frame_state: *mut FrameState,
+) -> Result {
+    layer().wait_frame(session, frame_wait_info, frame_state)
+}
+
+unsafe extern "system" fn xr_create_session(
+    instance: Instance,
+    create_info: *const SessionCreateInfo,
+    session: *mut Session,
+) -> Result {
+    layer_mut().create_session(instance, create_info, session)
+}
+
+unsafe extern "system" fn xr_destroy_session(session: Session) -> Result {
+    layer_mut().destroy_session(session)
+}
+
+unsafe extern "system" fn xr_poll_event(
+    instance: Instance,
+    event_data: *mut EventDataBuffer,
+) -> Result {
+    layer().poll_event(instance, event_data)
+}
+
+unsafe extern "system" fn xr_create_face_tracker2_fb(
+    session: Session,
+    create_info: *const sys::FaceTrackerCreateInfo2FB,
+    face_tracker: *mut sys::FaceTracker2FB,
+) -> Result {
+    layer_mut().create_face_tracker2_fb(session, create_info, face_tracker)
+}
+
+unsafe extern "system" fn xr_destroy_face_tracker2_fb(face_tracker: sys::FaceTracker2FB) -> Result {
+    layer_mut().destroy_face_tracker2_fb(face_tracker)
+}
+
+unsafe extern "system" fn xr_get_face_expression_weights2_fb(
+    face_tracker: sys::FaceTracker2FB,
+    expression_info: *const sys::FaceExpressionInfo2FB,
+    expression_weights: *mut sys::FaceExpressionWeights2FB,
+) -> Result {
+    layer().get_face_expression_weights2_fb(face_tracker, expression_info, expression_weights)
+}
+
+unsafe extern "system" fn xr_create_facial_tracker_htc(
+    session: Session,
+    create_info: *const FacialTrackerCreateInfoHTC,
+    facial_tracker: *mut FacialTrackerHTC,
+) -> Result {
+    layer_mut().create_facial_tracker_htc(session, create_info, facial_tracker)
+}
+
+unsafe extern "system" fn xr_destroy_facial_tracker_htc(
+    facial_tracker: FacialTrackerHTC,
+) -> Result {
+    layer_mut().destroy_facial_tracker_htc(facial_tracker)
+}
+
+unsafe extern "system" fn xr_get_facial_expressions_htc(
+    facial_tracker: FacialTrackerHTC,
+    facial_expressions: *mut FacialExpressionsHTC,
+) -> Result {
+    layer().get_facial_expressions_htc(facial_tracker, facial_expressions)
+}
+
+unsafe extern "system" fn xr_get_foveation_eye_tracked_state_meta(
+    session: Session,
+    foveation_state: *mut FoveationEyeTrackedStateMETA,
+) -> Result {
+    layer().get_foveation_eye_tracked_state_meta(session, foveation_state)
+}
+
+unsafe extern "system" fn xr_get_raw_eye_data_etvr(
+    session: Session,
+    get_info: *const raw_eye_data::RawEyeDataGetInfoETVR,
+    eye_data: *mut raw_eye_data::RawEyeDataETVR,
+) -> Result {
+    layer().get_raw_eye_data_etvr(session, get_info, eye_data)
+}
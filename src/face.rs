@@ -0,0 +1,406 @@
+use openxr_sys::LipExpressionHTC;
+
+use crate::{
+    pose,
+    source::{EyeGazeData, MouthData},
+    sys::face_expression2 as fe2,
+};
+
+/// Lower face expressions sent by Project Babble, named after the VRCFT unified expressions.
+#[allow(dead_code)]
+pub mod mouth {
+    pub const CHEEK_PUFF_LEFT: usize = 0;
+    pub const CHEEK_PUFF_RIGHT: usize = 1;
+    pub const CHEEK_SUCK_LEFT: usize = 2;
+    pub const CHEEK_SUCK_RIGHT: usize = 3;
+    pub const JAW_OPEN: usize = 4;
+    pub const JAW_FORWARD: usize = 5;
+    pub const JAW_LEFT: usize = 6;
+    pub const JAW_RIGHT: usize = 7;
+    pub const NOSE_SNEER_LEFT: usize = 8;
+    pub const NOSE_SNEER_RIGHT: usize = 9;
+    pub const MOUTH_FUNNEL: usize = 10;
+    pub const MOUTH_PUCKER: usize = 11;
+    pub const MOUTH_LEFT: usize = 12;
+    pub const MOUTH_RIGHT: usize = 13;
+    pub const MOUTH_ROLL_UPPER: usize = 14;
+    pub const MOUTH_ROLL_LOWER: usize = 15;
+    pub const MOUTH_SHRUG_UPPER: usize = 16;
+    pub const MOUTH_SHRUG_LOWER: usize = 17;
+    pub const MOUTH_CLOSE: usize = 18;
+    pub const MOUTH_SMILE_LEFT: usize = 19;
+    pub const MOUTH_SMILE_RIGHT: usize = 20;
+    pub const MOUTH_FROWN_LEFT: usize = 21;
+    pub const MOUTH_FROWN_RIGHT: usize = 22;
+    pub const MOUTH_DIMPLE_LEFT: usize = 23;
+    pub const MOUTH_DIMPLE_RIGHT: usize = 24;
+    pub const MOUTH_UPPER_UP_LEFT: usize = 25;
+    pub const MOUTH_UPPER_UP_RIGHT: usize = 26;
+    pub const MOUTH_LOWER_DOWN_LEFT: usize = 27;
+    pub const MOUTH_LOWER_DOWN_RIGHT: usize = 28;
+    pub const MOUTH_PRESS_LEFT: usize = 29;
+    pub const MOUTH_PRESS_RIGHT: usize = 30;
+    pub const MOUTH_STRETCH_LEFT: usize = 31;
+    pub const MOUTH_STRETCH_RIGHT: usize = 32;
+    pub const TONGUE_OUT: usize = 33;
+    pub const TONGUE_UP: usize = 34;
+    pub const TONGUE_DOWN: usize = 35;
+    pub const TONGUE_LEFT: usize = 36;
+    pub const TONGUE_RIGHT: usize = 37;
+    pub const TONGUE_ROLL: usize = 38;
+    pub const TONGUE_BEND_DOWN: usize = 39;
+    pub const TONGUE_CURL_UP: usize = 40;
+    pub const TONGUE_SQUISH: usize = 41;
+    pub const TONGUE_FLAT: usize = 42;
+    pub const TONGUE_TWIST_LEFT: usize = 43;
+    pub const TONGUE_TWIST_RIGHT: usize = 44;
+
+    pub const COUNT: usize = 45;
+
+    /// OSC address suffixes for each expression.
+    pub const NAMES: [&str; COUNT] = [
+        "cheekPuffLeft",
+        "cheekPuffRight",
+        "cheekSuckLeft",
+        "cheekSuckRight",
+        "jawOpen",
+        "jawForward",
+        "jawLeft",
+        "jawRight",
+        "noseSneerLeft",
+        "noseSneerRight",
+        "mouthFunnel",
+        "mouthPucker",
+        "mouthLeft",
+        "mouthRight",
+        "mouthRollUpper",
+        "mouthRollLower",
+        "mouthShrugUpper",
+        "mouthShrugLower",
+        "mouthClose",
+        "mouthSmileLeft",
+        "mouthSmileRight",
+        "mouthFrownLeft",
+        "mouthFrownRight",
+        "mouthDimpleLeft",
+        "mouthDimpleRight",
+        "mouthUpperUpLeft",
+        "mouthUpperUpRight",
+        "mouthLowerDownLeft",
+        "mouthLowerDownRight",
+        "mouthPressLeft",
+        "mouthPressRight",
+        "mouthStretchLeft",
+        "mouthStretchRight",
+        "tongueOut",
+        "tongueUp",
+        "tongueDown",
+        "tongueLeft",
+        "tongueRight",
+        "tongueRoll",
+        "tongueBendDown",
+        "tongueCurlUp",
+        "tongueSquish",
+        "tongueFlat",
+        "tongueTwistLeft",
+        "tongueTwistRight",
+    ];
+}
+
+/// Gaze angle that maps to a full `EYES_LOOK_*` weight.
+const MAX_LOOK_ANGLE: f32 = 30.0 * std::f32::consts::PI / 180.0;
+
+/// Fill the eye region of `XrFaceExpressionWeights2FB::weights` from the eye tracker data.
+///
+/// Weights outside the eye region are left untouched.
+pub fn fill_eye_expressions2(weights: &mut [f32], eye_gaze_data: &EyeGazeData) {
+    let look = |angle: f32| (angle / MAX_LOOK_ANGLE).clamp(0.0, 1.0);
+    let (l_pitch, l_yaw) = pose::angles_from_direction(eye_gaze_data.l_direction);
+    let (r_pitch, r_yaw) = pose::angles_from_direction(eye_gaze_data.r_direction);
+
+    // Positive pitch looks up, positive yaw looks left.
+    weights[fe2::EYES_LOOK_UP_L] = look(l_pitch);
+    weights[fe2::EYES_LOOK_UP_R] = look(r_pitch);
+    weights[fe2::EYES_LOOK_DOWN_L] = look(-l_pitch);
+    weights[fe2::EYES_LOOK_DOWN_R] = look(-r_pitch);
+    weights[fe2::EYES_LOOK_LEFT_L] = look(l_yaw);
+    weights[fe2::EYES_LOOK_LEFT_R] = look(r_yaw);
+    weights[fe2::EYES_LOOK_RIGHT_L] = look(-l_yaw);
+    weights[fe2::EYES_LOOK_RIGHT_R] = look(-r_yaw);
+
+    let weight = |value: f32| value.clamp(0.0, 1.0);
+    weights[fe2::EYES_CLOSED_L] = weight(1.0 - eye_gaze_data.l_openness);
+    weights[fe2::EYES_CLOSED_R] = weight(1.0 - eye_gaze_data.r_openness);
+    weights[fe2::UPPER_LID_RAISER_L] = weight(eye_gaze_data.l_widen);
+    weights[fe2::UPPER_LID_RAISER_R] = weight(eye_gaze_data.r_widen);
+    weights[fe2::LID_TIGHTENER_L] = weight(eye_gaze_data.l_squeeze);
+    weights[fe2::LID_TIGHTENER_R] = weight(eye_gaze_data.r_squeeze);
+}
+
+/// Fill the lower face region of `XrFaceExpressionWeights2FB::weights` from the mouth tracker data.
+pub fn fill_mouth_expressions2(weights: &mut [f32], mouth_data: &MouthData) {
+    let m = &mouth_data.weights;
+
+    weights[fe2::CHEEK_PUFF_L] = m[mouth::CHEEK_PUFF_LEFT];
+    weights[fe2::CHEEK_PUFF_R] = m[mouth::CHEEK_PUFF_RIGHT];
+    weights[fe2::CHEEK_SUCK_L] = m[mouth::CHEEK_SUCK_LEFT];
+    weights[fe2::CHEEK_SUCK_R] = m[mouth::CHEEK_SUCK_RIGHT];
+    weights[fe2::JAW_DROP] = m[mouth::JAW_OPEN];
+    weights[fe2::JAW_THRUST] = m[mouth::JAW_FORWARD];
+    weights[fe2::JAW_SIDEWAYS_LEFT] = m[mouth::JAW_LEFT];
+    weights[fe2::JAW_SIDEWAYS_RIGHT] = m[mouth::JAW_RIGHT];
+    weights[fe2::NOSE_WRINKLER_L] = m[mouth::NOSE_SNEER_LEFT];
+    weights[fe2::NOSE_WRINKLER_R] = m[mouth::NOSE_SNEER_RIGHT];
+    weights[fe2::LIP_FUNNELER_LB] = m[mouth::MOUTH_FUNNEL];
+    weights[fe2::LIP_FUNNELER_LT] = m[mouth::MOUTH_FUNNEL];
+    weights[fe2::LIP_FUNNELER_RB] = m[mouth::MOUTH_FUNNEL];
+    weights[fe2::LIP_FUNNELER_RT] = m[mouth::MOUTH_FUNNEL];
+    weights[fe2::LIP_PUCKER_L] = m[mouth::MOUTH_PUCKER];
+    weights[fe2::LIP_PUCKER_R] = m[mouth::MOUTH_PUCKER];
+    weights[fe2::MOUTH_LEFT] = m[mouth::MOUTH_LEFT];
+    weights[fe2::MOUTH_RIGHT] = m[mouth::MOUTH_RIGHT];
+    weights[fe2::LIP_SUCK_LT] = m[mouth::MOUTH_ROLL_UPPER];
+    weights[fe2::LIP_SUCK_RT] = m[mouth::MOUTH_ROLL_UPPER];
+    weights[fe2::LIP_SUCK_LB] = m[mouth::MOUTH_ROLL_LOWER];
+    weights[fe2::LIP_SUCK_RB] = m[mouth::MOUTH_ROLL_LOWER];
+    weights[fe2::CHIN_RAISER_T] = m[mouth::MOUTH_SHRUG_UPPER];
+    weights[fe2::CHIN_RAISER_B] = m[mouth::MOUTH_SHRUG_LOWER];
+    weights[fe2::LIPS_TOWARD] = m[mouth::MOUTH_CLOSE];
+    weights[fe2::LIP_CORNER_PULLER_L] = m[mouth::MOUTH_SMILE_LEFT];
+    weights[fe2::LIP_CORNER_PULLER_R] = m[mouth::MOUTH_SMILE_RIGHT];
+    weights[fe2::LIP_CORNER_DEPRESSOR_L] = m[mouth::MOUTH_FROWN_LEFT];
+    weights[fe2::LIP_CORNER_DEPRESSOR_R] = m[mouth::MOUTH_FROWN_RIGHT];
+    weights[fe2::DIMPLER_L] = m[mouth::MOUTH_DIMPLE_LEFT];
+    weights[fe2::DIMPLER_R] = m[mouth::MOUTH_DIMPLE_RIGHT];
+    weights[fe2::UPPER_LIP_RAISER_L] = m[mouth::MOUTH_UPPER_UP_LEFT];
+    weights[fe2::UPPER_LIP_RAISER_R] = m[mouth::MOUTH_UPPER_UP_RIGHT];
+    weights[fe2::LOWER_LIP_DEPRESSOR_L] = m[mouth::MOUTH_LOWER_DOWN_LEFT];
+    weights[fe2::LOWER_LIP_DEPRESSOR_R] = m[mouth::MOUTH_LOWER_DOWN_RIGHT];
+    weights[fe2::LIP_PRESSOR_L] = m[mouth::MOUTH_PRESS_LEFT];
+    weights[fe2::LIP_PRESSOR_R] = m[mouth::MOUTH_PRESS_RIGHT];
+    weights[fe2::LIP_STRETCHER_L] = m[mouth::MOUTH_STRETCH_LEFT];
+    weights[fe2::LIP_STRETCHER_R] = m[mouth::MOUTH_STRETCH_RIGHT];
+    weights[fe2::TONGUE_OUT] = m[mouth::TONGUE_OUT];
+    weights[fe2::TONGUE_TIP_ALVEOLAR] = m[mouth::TONGUE_UP];
+}
+
+/// Fill `XrFacialExpressionsHTC::expression_weightings` of a lip tracker from the mouth tracker data.
+pub fn fill_lip_expressions_htc(weights: &mut [f32], mouth_data: &MouthData) {
+    let m = &mouth_data.weights;
+    let mut set = |expression: LipExpressionHTC, value: f32| {
+        weights[expression.into_raw() as usize] = value;
+    };
+
+    set(LipExpressionHTC::JAW_RIGHT, m[mouth::JAW_RIGHT]);
+    set(LipExpressionHTC::JAW_LEFT, m[mouth::JAW_LEFT]);
+    set(LipExpressionHTC::JAW_FORWARD, m[mouth::JAW_FORWARD]);
+    set(LipExpressionHTC::JAW_OPEN, m[mouth::JAW_OPEN]);
+    set(LipExpressionHTC::MOUTH_APE_SHAPE, m[mouth::MOUTH_CLOSE]);
+    set(LipExpressionHTC::MOUTH_UPPER_RIGHT, m[mouth::MOUTH_RIGHT]);
+    set(LipExpressionHTC::MOUTH_UPPER_LEFT, m[mouth::MOUTH_LEFT]);
+    set(LipExpressionHTC::MOUTH_LOWER_RIGHT, m[mouth::MOUTH_RIGHT]);
+    set(LipExpressionHTC::MOUTH_LOWER_LEFT, m[mouth::MOUTH_LEFT]);
+    set(
+        LipExpressionHTC::MOUTH_UPPER_OVERTURN,
+        m[mouth::MOUTH_FUNNEL],
+    );
+    set(
+        LipExpressionHTC::MOUTH_LOWER_OVERTURN,
+        m[mouth::MOUTH_FUNNEL],
+    );
+    set(LipExpressionHTC::MOUTH_POUT, m[mouth::MOUTH_PUCKER]);
+    set(
+        LipExpressionHTC::MOUTH_SMILE_RIGHT,
+        m[mouth::MOUTH_SMILE_RIGHT],
+    );
+    set(
+        LipExpressionHTC::MOUTH_SMILE_LEFT,
+        m[mouth::MOUTH_SMILE_LEFT],
+    );
+    set(
+        LipExpressionHTC::MOUTH_SAD_RIGHT,
+        m[mouth::MOUTH_FROWN_RIGHT],
+    );
+    set(LipExpressionHTC::MOUTH_SAD_LEFT, m[mouth::MOUTH_FROWN_LEFT]);
+    set(
+        LipExpressionHTC::CHEEK_PUFF_RIGHT,
+        m[mouth::CHEEK_PUFF_RIGHT],
+    );
+    set(LipExpressionHTC::CHEEK_PUFF_LEFT, m[mouth::CHEEK_PUFF_LEFT]);
+    set(
+        LipExpressionHTC::CHEEK_SUCK,
+        m[mouth::CHEEK_SUCK_LEFT].max(m[mouth::CHEEK_SUCK_RIGHT]),
+    );
+    set(
+        LipExpressionHTC::MOUTH_UPPER_UPRIGHT,
+        m[mouth::MOUTH_UPPER_UP_RIGHT],
+    );
+    set(
+        LipExpressionHTC::MOUTH_UPPER_UPLEFT,
+        m[mouth::MOUTH_UPPER_UP_LEFT],
+    );
+    set(
+        LipExpressionHTC::MOUTH_LOWER_DOWNRIGHT,
+        m[mouth::MOUTH_LOWER_DOWN_RIGHT],
+    );
+    set(
+        LipExpressionHTC::MOUTH_LOWER_DOWNLEFT,
+        m[mouth::MOUTH_LOWER_DOWN_LEFT],
+    );
+    set(
+        LipExpressionHTC::MOUTH_UPPER_INSIDE,
+        m[mouth::MOUTH_ROLL_UPPER],
+    );
+    set(
+        LipExpressionHTC::MOUTH_LOWER_INSIDE,
+        m[mouth::MOUTH_ROLL_LOWER],
+    );
+    set(
+        LipExpressionHTC::MOUTH_LOWER_OVERLAY,
+        m[mouth::MOUTH_SHRUG_LOWER],
+    );
+    set(LipExpressionHTC::TONGUE_LONGSTEP1, m[mouth::TONGUE_OUT]);
+    set(LipExpressionHTC::TONGUE_LONGSTEP2, m[mouth::TONGUE_OUT]);
+    set(LipExpressionHTC::TONGUE_LEFT, m[mouth::TONGUE_LEFT]);
+    set(LipExpressionHTC::TONGUE_RIGHT, m[mouth::TONGUE_RIGHT]);
+    set(LipExpressionHTC::TONGUE_UP, m[mouth::TONGUE_UP]);
+    set(LipExpressionHTC::TONGUE_DOWN, m[mouth::TONGUE_DOWN]);
+    set(LipExpressionHTC::TONGUE_ROLL, m[mouth::TONGUE_ROLL]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sys::FACE_EXPRESSION2_COUNT_FB;
+
+    /// Weights not written by `fill_eye_expressions2`.
+    const UNTOUCHED: f32 = -1.0;
+
+    const EYE_REGION: [usize; 14] = [
+        fe2::EYES_CLOSED_L,
+        fe2::EYES_CLOSED_R,
+        fe2::EYES_LOOK_DOWN_L,
+        fe2::EYES_LOOK_DOWN_R,
+        fe2::EYES_LOOK_LEFT_L,
+        fe2::EYES_LOOK_LEFT_R,
+        fe2::EYES_LOOK_RIGHT_L,
+        fe2::EYES_LOOK_RIGHT_R,
+        fe2::EYES_LOOK_UP_L,
+        fe2::EYES_LOOK_UP_R,
+        fe2::LID_TIGHTENER_L,
+        fe2::LID_TIGHTENER_R,
+        fe2::UPPER_LID_RAISER_L,
+        fe2::UPPER_LID_RAISER_R,
+    ];
+
+    fn eye_weights(eye_gaze_data: &EyeGazeData) -> [f32; FACE_EXPRESSION2_COUNT_FB] {
+        let mut weights = [UNTOUCHED; FACE_EXPRESSION2_COUNT_FB];
+        fill_eye_expressions2(&mut weights, eye_gaze_data);
+        weights
+    }
+
+    fn degrees(pitch: f32, yaw: f32) -> [f32; 3] {
+        pose::direction_from_angles(pitch.to_radians(), yaw.to_radians())
+    }
+
+    #[test]
+    fn eye_region_mapping() {
+        // Sample, then the expected non-zero eye region weights.
+        let cases: [(EyeGazeData, &[(usize, f32)]); 9] = [
+            (EyeGazeData::default(), &[]),
+            (
+                EyeGazeData {
+                    l_direction: degrees(15.0, 0.0),
+                    r_direction: degrees(15.0, 0.0),
+                    ..Default::default()
+                },
+                &[(fe2::EYES_LOOK_UP_L, 0.5), (fe2::EYES_LOOK_UP_R, 0.5)],
+            ),
+            (
+                EyeGazeData {
+                    l_direction: degrees(-15.0, 0.0),
+                    ..Default::default()
+                },
+                &[(fe2::EYES_LOOK_DOWN_L, 0.5)],
+            ),
+            (
+                EyeGazeData {
+                    r_direction: degrees(0.0, 15.0),
+                    ..Default::default()
+                },
+                &[(fe2::EYES_LOOK_LEFT_R, 0.5)],
+            ),
+            (
+                EyeGazeData {
+                    l_direction: degrees(0.0, -60.0),
+                    ..Default::default()
+                },
+                &[(fe2::EYES_LOOK_RIGHT_L, 1.0)],
+            ),
+            (
+                EyeGazeData {
+                    l_openness: 0.25,
+                    r_openness: 0.0,
+                    ..Default::default()
+                },
+                &[(fe2::EYES_CLOSED_L, 0.75), (fe2::EYES_CLOSED_R, 1.0)],
+            ),
+            (
+                EyeGazeData {
+                    l_widen: 0.3,
+                    r_squeeze: 0.6,
+                    ..Default::default()
+                },
+                &[(fe2::UPPER_LID_RAISER_L, 0.3), (fe2::LID_TIGHTENER_R, 0.6)],
+            ),
+            (
+                EyeGazeData {
+                    l_openness: 1.5,
+                    r_openness: -0.5,
+                    ..Default::default()
+                },
+                &[(fe2::EYES_CLOSED_R, 1.0)],
+            ),
+            (
+                EyeGazeData {
+                    l_widen: 2.0,
+                    r_widen: -1.0,
+                    l_squeeze: -0.5,
+                    r_squeeze: 1.5,
+                    ..Default::default()
+                },
+                &[(fe2::UPPER_LID_RAISER_L, 1.0), (fe2::LID_TIGHTENER_R, 1.0)],
+            ),
+        ];
+
+        for (i, (sample, expected)) in cases.iter().enumerate() {
+            let weights = eye_weights(sample);
+            for index in EYE_REGION {
+                let want = expected
+                    .iter()
+                    .find(|(e, _)| *e == index)
+                    .map_or(0.0, |(_, w)| *w);
+                assert!(
+                    (weights[index] - want).abs() < 1e-5,
+                    "case {i}: weight {index} is {}, not {want}",
+                    weights[index]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn weights_outside_the_eye_region_are_untouched() {
+        let weights = eye_weights(&EyeGazeData {
+            l_direction: degrees(10.0, 10.0),
+            l_openness: 0.5,
+            r_widen: 0.5,
+            ..Default::default()
+        });
+        for (index, weight) in weights.iter().enumerate() {
+            if !EYE_REGION.contains(&index) {
+                assert_eq!(*weight, UNTOUCHED, "weight {index}");
+            }
+        }
+    }
+}
@@ -0,0 +1,336 @@
+use std::time::{Duration, SystemTime};
+
+use crate::{config::Config, pose, source::EyeGazeData};
+
+/// Samples further apart than this restart the filter instead of smoothing across the gap.
+const MAX_SAMPLE_GAP: Duration = Duration::from_millis(500);
+
+/// Smoothing applied to the gaze directions before they reach a hook.
+///
+/// Configured from the `<section>.type` key, `none`, `one_euro` or `kalman`, and the parameters
+/// of that filter in the same section. Each component of the unit directions is filtered on its
+/// own; near the centre of the view a change of a component is about the change of the angle in
+/// radians, so the parameters are in radians and radians per second.
+#[derive(Debug, Clone, Copy)]
+enum FilterKind {
+    None,
+    OneEuro {
+        /// Cutoff frequency in Hz when the gaze is still.
+        min_cutoff: f32,
+        /// How much the cutoff rises with the gaze speed.
+        beta: f32,
+        /// Cutoff frequency in Hz of the speed estimate.
+        d_cutoff: f32,
+    },
+    /// Constant velocity model.
+    Kalman {
+        /// Standard deviation of the gaze acceleration, in radians per second squared.
+        process_noise: f32,
+        /// Standard deviation of the tracker noise, in radians.
+        measurement_noise: f32,
+    },
+}
+
+impl FilterKind {
+    fn from_config(config: &Config, section: &str) -> FilterKind {
+        let key = |name: &str| format!("{section}.{name}");
+
+        match config.get_str(&key("type")) {
+            Some("none") | None => FilterKind::None,
+            Some("one_euro") => FilterKind::OneEuro {
+                min_cutoff: config.get(&key("min_cutoff"), 1.0),
+                beta: config.get(&key("beta"), 10.0),
+                d_cutoff: config.get(&key("d_cutoff"), 1.0),
+            },
+            Some("kalman") => FilterKind::Kalman {
+                process_noise: config.get(&key("process_noise"), 100.0),
+                measurement_noise: config.get(&key("measurement_noise"), 0.01),
+            },
+            Some(kind) => {
+                println!("Unknown {section} type {kind}, not filtering");
+                FilterKind::None
+            }
+        }
+    }
+}
+
+/// Filter state of a single direction component.
+#[derive(Debug, Clone, Copy)]
+enum ChannelState {
+    OneEuro {
+        value: f32,
+        velocity: f32,
+    },
+    Kalman {
+        /// Angle and angular velocity.
+        x: [f32; 2],
+        /// Covariance of `x`.
+        p: [[f32; 2]; 2],
+    },
+}
+
+impl ChannelState {
+    fn new(kind: FilterKind, value: f32) -> Option<ChannelState> {
+        match kind {
+            FilterKind::None => None,
+            FilterKind::OneEuro { .. } => Some(ChannelState::OneEuro {
+                value,
+                velocity: 0.0,
+            }),
+            FilterKind::Kalman {
+                measurement_noise, ..
+            } => Some(ChannelState::Kalman {
+                x: [value, 0.0],
+                p: [[measurement_noise * measurement_noise, 0.0], [0.0, 1.0]],
+            }),
+        }
+    }
+
+    /// The filtered value.
+    fn value(&self) -> f32 {
+        match self {
+            ChannelState::OneEuro { value, .. } => *value,
+            ChannelState::Kalman { x, .. } => x[0],
+        }
+    }
+
+    /// Add a measurement taken `dt` seconds after the previous one.
+    fn step(&mut self, kind: FilterKind, measurement: f32, dt: f32) {
+        match (self, kind) {
+            (
+                ChannelState::OneEuro { value, velocity },
+                FilterKind::OneEuro {
+                    min_cutoff,
+                    beta,
+                    d_cutoff,
+                },
+            ) => {
+                let alpha = |cutoff: f32| {
+                    let tau = 1.0 / (std::f32::consts::TAU * cutoff);
+                    1.0 / (1.0 + tau / dt)
+                };
+
+                let raw_velocity = (measurement - *value) / dt;
+                *velocity += alpha(d_cutoff) * (raw_velocity - *velocity);
+
+                let cutoff = min_cutoff + beta * velocity.abs();
+                *value += alpha(cutoff) * (measurement - *value);
+            }
+            (
+                ChannelState::Kalman { x, p },
+                FilterKind::Kalman {
+                    process_noise,
+                    measurement_noise,
+                },
+            ) => {
+                // Predict.
+                let q = process_noise * process_noise;
+                x[0] += x[1] * dt;
+                let p00 = p[0][0] + dt * (p[1][0] + p[0][1]) + dt * dt * p[1][1];
+                let p01 = p[0][1] + dt * p[1][1];
+                let p10 = p[1][0] + dt * p[1][1];
+                let p11 = p[1][1];
+                *p = [
+                    [p00 + q * dt.powi(3) / 3.0, p01 + q * dt.powi(2) / 2.0],
+                    [p10 + q * dt.powi(2) / 2.0, p11 + q * dt],
+                ];
+
+                // Update.
+                let s = p[0][0] + measurement_noise * measurement_noise;
+                let k = [p[0][0] / s, p[1][0] / s];
+                let residual = measurement - x[0];
+                x[0] += k[0] * residual;
+                x[1] += k[1] * residual;
+                *p = [
+                    [(1.0 - k[0]) * p[0][0], (1.0 - k[0]) * p[0][1]],
+                    [p[1][0] - k[1] * p[0][0], p[1][1] - k[1] * p[0][1]],
+                ];
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Smooths the gaze direction of both eyes from a gaze source.
+///
+/// Every sample in the source history is fed to the filter once, even if the hooks are called
+/// less often than the tracker sends data.
+pub struct GazeFilter {
+    kind: FilterKind,
+    /// Left direction x, y and z, then right direction x, y and z.
+    channels: [Option<ChannelState>; 6],
+    last_sample_time: Option<SystemTime>,
+}
+
+impl GazeFilter {
+    pub fn new(config: &Config, section: &str) -> GazeFilter {
+        GazeFilter {
+            kind: FilterKind::from_config(config, section),
+            channels: [None; 6],
+            last_sample_time: None,
+        }
+    }
+
+    /// The `latest` gaze with filtered directions, after feeding the filter the new samples of
+    /// `history`, oldest first.
+    pub fn update(&mut self, latest: EyeGazeData, history: &[EyeGazeData]) -> EyeGazeData {
+        if let FilterKind::None = self.kind {
+            return latest;
+        }
+
+        if self.last_sample_time != Some(latest.time) {
+            let last_sample_time = self.last_sample_time;
+            let new_samples = history
+                .iter()
+                .skip_while(|s| last_sample_time.is_some_and(|t| s.time <= t));
+            for sample in new_samples {
+                self.step(sample);
+            }
+        }
+
+        // Filtering the components separately shortens the vectors a little.
+        let [l_x, l_y, l_z, r_x, r_y, r_z] = self.output_components(&latest);
+        EyeGazeData {
+            l_direction: pose::normalize_direction([l_x, l_y, l_z]),
+            r_direction: pose::normalize_direction([r_x, r_y, r_z]),
+            ..latest
+        }
+    }
+
+    fn step(&mut self, sample: &EyeGazeData) {
+        let measurements = components(sample);
+
+        let dt = self
+            .last_sample_time
+            .and_then(|t| sample.time.duration_since(t).ok())
+            .filter(|dt| !dt.is_zero() && *dt < MAX_SAMPLE_GAP);
+        self.last_sample_time = Some(sample.time);
+
+        for (channel, measurement) in self.channels.iter_mut().zip(measurements) {
+            match (channel.as_mut(), dt) {
+                (Some(state), Some(dt)) => {
+                    state.step(self.kind, measurement, dt.as_secs_f32());
+                }
+                _ => *channel = ChannelState::new(self.kind, measurement),
+            }
+        }
+    }
+
+    fn output_components(&self, latest: &EyeGazeData) -> [f32; 6] {
+        let mut values = components(latest);
+        for (value, channel) in values.iter_mut().zip(&self.channels) {
+            if let Some(channel) = channel {
+                *value = channel.value();
+            }
+        }
+        values
+    }
+}
+
+fn components(sample: &EyeGazeData) -> [f32; 6] {
+    let [l_x, l_y, l_z] = sample.l_direction;
+    let [r_x, r_y, r_z] = sample.r_direction;
+    [l_x, l_y, l_z, r_x, r_y, r_z]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f32 = 1.0 / 120.0;
+
+    fn one_euro(beta: f32) -> FilterKind {
+        FilterKind::OneEuro {
+            min_cutoff: 1.0,
+            beta,
+            d_cutoff: 1.0,
+        }
+    }
+
+    fn kalman() -> FilterKind {
+        FilterKind::Kalman {
+            process_noise: 100.0,
+            measurement_noise: 0.01,
+        }
+    }
+
+    /// Filter a step from 0 to 1 for `duration` seconds sampled every `dt`.
+    fn step_response(kind: FilterKind, dt: f32, duration: f32) -> f32 {
+        let mut channel = ChannelState::new(kind, 0.0).unwrap();
+        for _ in 0..(duration / dt).round() as usize {
+            channel.step(kind, 1.0, dt);
+        }
+        channel.value()
+    }
+
+    #[test]
+    fn constant_input_converges() {
+        for kind in [one_euro(0.0), one_euro(10.0), kalman()] {
+            let mut channel = ChannelState::new(kind, 0.3).unwrap();
+            for _ in 0..240 {
+                channel.step(kind, 0.3, DT);
+            }
+            assert!((channel.value() - 0.3).abs() < 1e-6, "{kind:?}");
+
+            let value = step_response(kind, DT, 2.0);
+            assert!((value - 1.0).abs() < 1e-3, "{kind:?} {value}");
+        }
+    }
+
+    #[test]
+    fn one_euro_without_beta_lags_like_low_pass() {
+        // A first order low-pass reaches 1 - 1/e of a step after its time constant.
+        let tau = 1.0 / std::f32::consts::TAU;
+        let value = step_response(one_euro(0.0), 0.001, tau);
+        assert!((value - (1.0 - (-1.0f32).exp())).abs() < 0.01, "{value}");
+        assert!(step_response(one_euro(0.0), 0.001, 5.0 * tau) > 0.99);
+    }
+
+    #[test]
+    fn one_euro_beta_reduces_lag() {
+        let slow = step_response(one_euro(0.0), DT, 0.05);
+        let fast = step_response(one_euro(10.0), DT, 0.05);
+        assert!(fast > slow + 0.2, "{fast} {slow}");
+    }
+
+    #[test]
+    fn kalman_tracks_step() {
+        // The tracker noise is small compared with the step, so it's followed within a few
+        // samples, then settles after overshooting on the estimated velocity.
+        assert!(step_response(kalman(), DT, 5.0 * DT) > 0.9);
+        assert!((step_response(kalman(), DT, 1.0) - 1.0).abs() < 0.01);
+    }
+
+    fn sample(at_ms: u64, x: f32) -> EyeGazeData {
+        EyeGazeData {
+            l_direction: [x, 0.0, -1.0],
+            time: SystemTime::UNIX_EPOCH + Duration::from_millis(at_ms),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn long_gap_resets_filter() {
+        let gap = MAX_SAMPLE_GAP.as_millis() as u64;
+        for kind in [one_euro(0.0), kalman()] {
+            let filter = |next_at: u64| {
+                let mut filter = GazeFilter {
+                    kind,
+                    channels: [None; 6],
+                    last_sample_time: None,
+                };
+                for i in 0..10 {
+                    filter.step(&sample(i * 8, 0.0));
+                }
+                let next = sample(72 + next_at, 1.0);
+                filter.step(&next);
+                filter.output_components(&next)[0]
+            };
+
+            // Smoothed across short gaps, restarted at the new value after long ones.
+            assert!(filter(8) < 1.0, "{kind:?}");
+            assert!(filter(gap / 2) < 1.0, "{kind:?}");
+            assert_eq!(filter(gap + 1), 1.0, "{kind:?}");
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use openxr_sys::{Fovf, Vector2f};
+use quaternion_core::Vector3;
+
+/// Project a gaze direction onto a view, in normalized device coordinates.
+///
+/// (0, 0) is the center of the view and (1, 1) is its top right corner.
+pub fn foveation_center(direction: Vector3<f32>, fov: &Fovf) -> Vector2f {
+    let [x, y, z] = direction;
+
+    // Looking sideways or backwards, there's no point on the view plane.
+    if z >= -f32::EPSILON {
+        return Vector2f { x: 0.0, y: 0.0 };
+    }
+
+    let tan_x = x / -z;
+    let tan_y = y / -z;
+
+    let tan_left = fov.angle_left.tan();
+    let tan_right = fov.angle_right.tan();
+    let tan_up = fov.angle_up.tan();
+    let tan_down = fov.angle_down.tan();
+
+    Vector2f {
+        x: ((2.0 * tan_x - (tan_right + tan_left)) / (tan_right - tan_left)).clamp(-1.0, 1.0),
+        y: ((2.0 * tan_y - (tan_up + tan_down)) / (tan_up - tan_down)).clamp(-1.0, 1.0),
+    }
+}
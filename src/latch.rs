@@ -0,0 +1,221 @@
+//! Lock-free publication of the latest value from a producer thread to readers.
+
+use std::{
+    cell::UnsafeCell,
+    hint, ptr,
+    sync::{
+        atomic::{self, AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+/// A value written by one thread at a time and read without locks by any number of threads.
+///
+/// This is a seqlock with two copies of the value, like the Linux `seqcount_latch`. The writer
+/// first points readers at the second copy and updates the first, then points them back at the
+/// first and updates the second. A reader copies the copy the sequence number points at and
+/// retries if the sequence moved meanwhile. Readers never wait for a writer that's descheduled
+/// halfway through an update, they just read the other copy, and only retry when a write
+/// completed while they were reading.
+///
+/// Writers are serialized with a mutex that readers never touch.
+pub struct Latch<T: Copy> {
+    sequence: AtomicUsize,
+    values: [UnsafeCell<T>; 2],
+    writer: Mutex<()>,
+}
+
+// Readers only copy the values out, and writers are serialized.
+unsafe impl<T: Copy + Send> Sync for Latch<T> {}
+
+impl<T: Copy> Latch<T> {
+    pub fn new(value: T) -> Latch<T> {
+        Latch {
+            sequence: AtomicUsize::new(0),
+            values: [UnsafeCell::new(value), UnsafeCell::new(value)],
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// The latest completely written value.
+    pub fn read(&self) -> T {
+        loop {
+            let sequence = self.sequence.load(Ordering::Acquire);
+            // The copy may be torn if a writer gets to it, that's detected below and the copy
+            // is thrown away. The volatile read keeps the compiler from assuming it can't
+            // change.
+            let value = unsafe { ptr::read_volatile(self.values[sequence & 1].get()) };
+            atomic::fence(Ordering::Acquire);
+            if self.sequence.load(Ordering::Relaxed) == sequence {
+                return value;
+            }
+            hint::spin_loop();
+        }
+    }
+
+    /// Change the value with `update` and publish the result.
+    pub fn update<R>(&self, update: impl FnOnce(&mut T) -> R) -> R {
+        let _writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+
+        // Both copies are equal between updates and only this thread writes them.
+        let mut value = unsafe { *self.values[0].get() };
+        let result = update(&mut value);
+
+        for index in 0..2 {
+            // Point readers at the other copy, which the release publishes, before touching
+            // this one.
+            self.sequence.fetch_add(1, Ordering::Release);
+            atomic::fence(Ordering::Release);
+            unsafe { ptr::write_volatile(self.values[index].get(), value) };
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+    use crate::source::{EyeGazeData, History, EYE_GAZE_HISTORY_SIZE};
+
+    #[test]
+    fn reads_latest_update() {
+        let latch = Latch::new(1u32);
+        assert_eq!(latch.read(), 1);
+        latch.update(|v| *v += 1);
+        assert_eq!(latch.read(), 2);
+        assert_eq!(latch.update(|v| *v * 10), 20);
+        assert_eq!(latch.read(), 2);
+    }
+
+    #[test]
+    fn reads_are_never_torn() {
+        // Every element is written with the same value, so a torn read has different ones.
+        let latch = Arc::new(Latch::new([0u64; 64]));
+        let done = Arc::new(AtomicBool::new(false));
+        let writer = {
+            let (latch, done) = (latch.clone(), done.clone());
+            thread::spawn(move || {
+                let mut n = 0;
+                while !done.load(Ordering::Relaxed) {
+                    n += 1;
+                    latch.update(|v| *v = [n; 64]);
+                }
+            })
+        };
+
+        let mut last = 0;
+        for _ in 0..200_000 {
+            let value = latch.read();
+            assert!(value.iter().all(|&x| x == value[0]), "torn read {value:?}");
+            assert!(value[0] >= last, "went back from {last} to {}", value[0]);
+            last = value[0];
+        }
+        done.store(true, Ordering::Relaxed);
+        writer.join().unwrap();
+    }
+
+    /// Per-call cost of reading the latest gaze sample, and the gaze history the frame loop
+    /// reads once per frame, while a receiver keeps updating them and stalls for a millisecond
+    /// in the middle of every update, compared with a mutex. Run with
+    /// `cargo test --release latch -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_read_under_writes() {
+        const STALL: Duration = Duration::from_millis(1);
+
+        fn run<R>(name: &str, reads: u32, write: impl Fn() + Send + 'static, read: impl Fn() -> R) {
+            let done = Arc::new(AtomicBool::new(false));
+            let writer = {
+                let done = done.clone();
+                thread::spawn(move || {
+                    while !done.load(Ordering::Relaxed) {
+                        write();
+                    }
+                })
+            };
+
+            let mut worst = Duration::ZERO;
+            let start = Instant::now();
+            for _ in 0..reads {
+                let call = Instant::now();
+                std::hint::black_box(read());
+                worst = worst.max(call.elapsed());
+            }
+            let total = start.elapsed();
+            done.store(true, Ordering::Relaxed);
+            writer.join().unwrap();
+
+            println!(
+                "{name}: {:.1} ns per read, worst {:.1} µs",
+                total.as_nanos() as f64 / reads as f64,
+                worst.as_nanos() as f64 / 1000.0,
+            );
+        }
+
+        /// Read `value` with `read` through a latch and through a mutex while a writer keeps
+        /// changing it with `change`.
+        fn compare<T: Copy + Send + 'static, R>(
+            name: &str,
+            reads: u32,
+            value: T,
+            change: fn(&mut T),
+            read: fn(&T) -> R,
+        ) {
+            let latch = Arc::new(Latch::new(value));
+            let writer_latch = latch.clone();
+            run(
+                &format!("{name} latch"),
+                reads,
+                move || {
+                    writer_latch.update(|v| {
+                        change(v);
+                        thread::sleep(STALL);
+                    })
+                },
+                || read(&latch.read()),
+            );
+
+            let mutex = Arc::new(Mutex::new(value));
+            let writer_mutex = mutex.clone();
+            run(
+                &format!("{name} mutex"),
+                reads,
+                move || {
+                    let mut v = writer_mutex.lock().unwrap();
+                    change(&mut v);
+                    thread::sleep(STALL);
+                },
+                || read(&mutex.lock().unwrap()),
+            );
+        }
+
+        compare(
+            "gaze",
+            2_000_000,
+            EyeGazeData::default(),
+            |v| v.l_openness = 1.0 - v.l_openness,
+            |v| *v,
+        );
+
+        let mut history = History::new();
+        for _ in 0..EYE_GAZE_HISTORY_SIZE {
+            history.push(EyeGazeData::default());
+        }
+        compare(
+            "history",
+            100_000,
+            history,
+            |h| h.push(EyeGazeData::default()),
+            History::samples,
+        );
+    }
+}
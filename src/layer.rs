@@ -1,20 +1,52 @@
 use std::{
-    collections::HashMap,
-    ffi::{c_char, CStr},
-    time::{Duration, SystemTime},
+    collections::{HashMap, HashSet, VecDeque},
+    ffi::{c_char, CStr, CString},
+    sync::Mutex,
+    time::{Duration, Instant},
 };
 
 use openxr_sys::{
-    pfn, Action, ActionSpaceCreateInfo, ActionStateGetInfo, ActionStatePose, BaseOutStructure,
-    ExtensionProperties, EyeGazeSampleTimeEXT, Instance, InteractionProfileSuggestedBinding, Path,
-    Quaternionf, Result, Session, Space, SpaceLocation, SpaceLocationFlags, StructureType,
-    SystemEyeGazeInteractionPropertiesEXT, SystemId, SystemProperties, Time, Vector3f, View,
-    ViewConfigurationType, ViewLocateInfo, ViewState,
+    pfn, Action, ActionSpaceCreateInfo, ActionStateBoolean, ActionStateGetInfo, ActionStatePose,
+    ActionStateVector2f, BaseOutStructure, EventDataBuffer, ExtensionProperties,
+    EyeGazeSampleTimeEXT, FacialExpressionsHTC, FacialTrackerCreateInfoHTC, FacialTrackerHTC,
+    FacialTrackingTypeHTC, FoveationEyeTrackedStateFlagsMETA, FoveationEyeTrackedStateMETA, Fovf,
+    FrameState, FrameWaitInfo, Instance, InteractionProfileSuggestedBinding, Path, Posef,
+    ReferenceSpaceCreateInfo, ReferenceSpaceType, Result, Session, SessionCreateInfo, Space,
+    SpaceLocation, SpaceLocationFlags, StructureType, SystemEyeGazeInteractionPropertiesEXT,
+    SystemFacialTrackingPropertiesHTC, SystemFoveationEyeTrackedPropertiesMETA, SystemId,
+    SystemProperties, Time, Vector2f, View, ViewLocateInfo, ViewState,
+    FACIAL_EXPRESSION_LIP_COUNT_HTC,
 };
 
 use once_cell::sync::Lazy;
-
-use crate::server::OSCServer;
+use quaternion_core::Vector3;
+
+use crate::{
+    calibration::{Calibration, GazeCorrection},
+    classify::{GazeClassifier, GazeEvent, ViewGaze},
+    config::Config,
+    face,
+    filter::GazeFilter,
+    foveation,
+    latch::Latch,
+    monocular::EyeValidity,
+    mounting::Mounting,
+    osc_output::OscOutput,
+    pose,
+    predict::Predictor,
+    presence::UserPresence,
+    pupil_offset::{self, PupilOffset},
+    raw_eye_data::{
+        self, EyeTorsionETVR, FixationPointETVR, GazeClassificationETVR, RawEyeDataETVR,
+        RawEyeDataGetInfoETVR, RawEyeStateETVR,
+    },
+    recenter::{ChordHold, RecenterChord},
+    source::{self, Command, ControllerSource, EyeGazeData, GazeSource},
+    staleness::Staleness,
+    sys::{self, FaceTracker2FB},
+    time,
+    vergence::{EyeFrames, Fixation, Vergence},
+};
 
 pub static mut INSTANCE: Lazy<OpenXRLayer> = Lazy::new(OpenXRLayer::new);
 
@@ -23,58 +55,311 @@ struct Extension {
     version: u32,
 }
 
-const ADVERTISED_EXTENSIONS: &[Extension] = &[Extension {
-    name: "XR_EXT_eye_gaze_interaction",
-    version: 1,
-}];
+const ADVERTISED_EXTENSIONS: &[Extension] = &[
+    Extension {
+        name: "XR_EXT_eye_gaze_interaction",
+        version: 1,
+    },
+    Extension {
+        name: "XR_FB_face_tracking2",
+        version: 1,
+    },
+    Extension {
+        name: "XR_HTC_facial_tracking",
+        version: 2,
+    },
+    Extension {
+        name: "XR_EXT_user_presence",
+        version: 1,
+    },
+    Extension {
+        name: "XR_META_foveation_eye_tracked",
+        version: 1,
+    },
+    Extension {
+        name: raw_eye_data::EXTENSION_NAME,
+        version: raw_eye_data::SPEC_VERSION,
+    },
+];
+
+/// Advertised extensions that are still enabled in the runtime if it supports them too. The layer
+/// only adds to them, like the eye tracked state of foveation.
+const FORWARDED_EXTENSIONS: &[&str] = &[
+    "XR_FB_face_tracking2",
+    "XR_EXT_user_presence",
+    "XR_META_foveation_eye_tracked",
+];
+
+/// Whether the extension is implemented by this layer.
+pub fn is_advertised_extension(name: &str) -> bool {
+    ADVERTISED_EXTENSIONS.iter().any(|e| e.name == name)
+}
+
+/// Whether the extension is implemented by this layer alone and should be hidden from a runtime
+/// that supports `runtime_extensions`, to avoid an `ERROR_EXTENSION_NOT_PRESENT` from it.
+pub fn is_layer_only_extension(name: &str, runtime_extensions: &[String]) -> bool {
+    is_advertised_extension(name)
+        && !(FORWARDED_EXTENSIONS.contains(&name) && runtime_extensions.iter().any(|e| e == name))
+}
+
+/// Extensions of the runtime and the layers after this one, using their
+/// `xrEnumerateInstanceExtensionProperties`.
+pub unsafe fn next_extensions(
+    enumerate: pfn::EnumerateInstanceExtensionProperties,
+    layer_name: *const c_char,
+) -> std::result::Result<Vec<ExtensionProperties>, Result> {
+    let mut count = 0;
+    let result = enumerate(layer_name, 0, &mut count, std::ptr::null_mut());
+    if result != Result::SUCCESS {
+        return Err(result);
+    }
+
+    let empty = ExtensionProperties {
+        ty: StructureType::EXTENSION_PROPERTIES,
+        next: std::ptr::null_mut(),
+        extension_name: [0; openxr_sys::MAX_EXTENSION_NAME_SIZE],
+        extension_version: 0,
+    };
+    let mut extensions = vec![empty; count as usize];
+    let result = enumerate(layer_name, count, &mut count, extensions.as_mut_ptr());
+    if result != Result::SUCCESS {
+        return Err(result);
+    }
+    extensions.truncate(count as usize);
+
+    Ok(extensions)
+}
+
+/// Name of an `XrExtensionProperties`.
+pub fn extension_name(extension: &ExtensionProperties) -> String {
+    let name = extension.extension_name.map(|c| c as u8);
+    CStr::from_bytes_until_nul(&name)
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
 
 pub struct OpenXRLayer {
     pub instance: Option<Instance>,
+    pub enabled_extensions: Vec<String>,
+    /// Enabled extensions that the runtime implements itself and gets to handle, see
+    /// `FORWARDED_EXTENSIONS`.
+    pub native_extensions: Vec<String>,
     pub get_instance_proc_addr: Option<pfn::GetInstanceProcAddr>,
     pub enumerate_instance_extensions_properties: Option<pfn::EnumerateInstanceExtensionProperties>,
     pub get_system_properties: Option<pfn::GetSystemProperties>,
     pub suggest_interaction_profile_bindings: Option<pfn::SuggestInteractionProfileBindings>,
     pub path_to_string: Option<pfn::PathToString>,
+    pub string_to_path: Option<pfn::StringToPath>,
+    pub create_reference_space: Option<pfn::CreateReferenceSpace>,
     pub create_action_space: Option<pfn::CreateActionSpace>,
     pub get_action_state_pose: Option<pfn::GetActionStatePose>,
+    pub get_action_state_boolean: Option<pfn::GetActionStateBoolean>,
+    pub get_action_state_vector2f: Option<pfn::GetActionStateVector2f>,
     pub locate_space: Option<pfn::LocateSpace>,
     pub locate_views: Option<pfn::LocateViews>,
+    pub create_session: Option<pfn::CreateSession>,
+    pub destroy_session: Option<pfn::DestroySession>,
+    pub poll_event: Option<pfn::PollEvent>,
+    pub wait_frame: Option<pfn::WaitFrame>,
 
     possible_spaces: HashMap<(Action, Path), Space>,
 
     eye_gaze_action: Option<Action>,
+    /// Gaze space without a subaction path, combining both eyes.
+    eye_gaze_space: Option<Space>,
     l_eye_gaze_space: Option<Space>,
     r_eye_gaze_space: Option<Space>,
 
-    start_time: SystemTime,
+    face_trackers2: HashSet<FaceTracker2FB>,
+    facial_trackers_htc: HashSet<u64>,
+    next_tracker_handle: u64,
+
+    sessions: Vec<Session>,
+    located_views: Latch<LocatedViews>,
+    user_presence: Mutex<UserPresence>,
+    pending_presence_events: Mutex<VecDeque<(Session, bool)>>,
+
+    source: Box<dyn GazeSource>,
+    /// The source again if it's driven by a controller thumbstick.
+    controller: Option<ControllerSource>,
+    frame_sampler: Mutex<FrameSampler>,
+    /// The calibration and recenter offset of `frame_sampler`, for the queries between frames.
+    gaze_correction: Latch<GazeCorrection>,
+    mounting: Mounting,
+    pupil_offset: PupilOffset,
+    eye_validity: EyeValidity,
+    /// Updated with the sample rate every frame.
+    staleness: Latch<Staleness>,
+    /// Report the head direction as an untracked gaze when the eye data is stale.
+    head_gaze_fallback: bool,
+    /// VIEW space of the current session, to locate the head.
+    view_space: Option<Space>,
+    vergence: Vergence,
+    recenter_chord: RecenterChord,
+    /// The app's actions bound to the thumbstick of `controller`, and to its click.
+    thumbstick_actions: HashSet<Action>,
+    thumbstick_click_actions: HashSet<Action>,
+    /// Subaction path of the hand with the thumbstick, once one of its actions is bound.
+    thumbstick_hand: Option<Path>,
+    classifier: GazeClassifier,
+    osc_output: Option<OscOutput>,
+    /// Gaze latched in `xrWaitFrame` for the last frames.
+    latched_frames: Latch<LatchedFrames>,
+}
+
+/// Frames further apart than this mean the frame loop stopped, and the gaze latched for the
+/// last frames is no longer used.
+const FRAME_GAZE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Frames latched at once, enough for the frames an engine has in flight.
+const LATCHED_FRAME_COUNT: usize = 4;
+
+/// The gaze used by every query for one frame, so the interaction pose, the views and the
+/// emulated extensions agree on the sample.
+#[derive(Debug, Clone, Copy)]
+struct FrameGaze {
+    /// `predictedDisplayTime` of the frame, `None` when sampled outside of a frame.
+    display_time: Option<Time>,
+    /// When the gaze was sampled.
+    latched_at: Instant,
+    /// Calibrated gaze smoothed by `pose_filter`, for the eye gaze pose and the extensions.
+    pose: EyeGazeData,
+    /// Calibrated gaze smoothed by `view_filter`, for the foveation centre and the fixation.
+    view: EyeGazeData,
+    /// Directions of `view` for the pupil offset, held or eased during saccades and blinks.
+    pupil_directions: [Vector3<f32>; 2],
+    /// Which eyes of `pose` and `view` are tracked.
+    valid: [bool; 2],
+}
+
+/// The gaze latched for the last frames, newest at `next - 1`.
+#[derive(Debug, Clone, Copy, Default)]
+struct LatchedFrames {
+    frames: [Option<FrameGaze>; LATCHED_FRAME_COUNT],
+    next: usize,
+}
+
+impl LatchedFrames {
+    fn push(&mut self, frame_gaze: FrameGaze) {
+        self.frames[self.next] = Some(frame_gaze);
+        self.next = (self.next + 1) % LATCHED_FRAME_COUNT;
+    }
+
+    /// The frame latched less than `FRAME_GAZE_TIMEOUT` before `now` with the display time
+    /// nearest to `time`, the newest one for no time.
+    fn nearest(&self, time: Option<Time>, now: Instant) -> Option<FrameGaze> {
+        let fresh = self
+            .frames
+            .iter()
+            .flatten()
+            .filter(|f| now.duration_since(f.latched_at) < FRAME_GAZE_TIMEOUT);
+        match time {
+            Some(time) => fresh.min_by_key(|f| {
+                f.display_time.map_or(u64::MAX, |t| {
+                    (t.as_nanos() - time.as_nanos()).unsigned_abs()
+                })
+            }),
+            None => fresh.max_by_key(|f| f.latched_at),
+        }
+        .copied()
+    }
+}
+
+/// Gaze state that changes with every frame, only used by `xrWaitFrame` and by queries
+/// outside of a frame loop.
+struct FrameSampler {
+    /// Smoothing of the eye gaze interaction pose and the face expressions.
+    pose_filter: GazeFilter,
+    /// Smoothing of the pupil offset applied in `xrLocateViews` and the foveation centre.
+    view_filter: GazeFilter,
+    predictor: Predictor,
+    view_gaze: ViewGaze,
+    /// Classification of the gaze at the last frame.
+    gaze_event: Option<GazeEvent>,
+    /// Calibration state, driven by the source's commands. What it applies is published in
+    /// `OpenXRLayer::gaze_correction`.
+    calibration: Calibration,
+    recenter_hold: ChordHold,
+}
 
-    server: OSCServer,
+/// What the last `xrLocateViews` call located.
+#[derive(Debug, Clone, Copy, Default)]
+struct LocatedViews {
+    /// Field of view of the stereo views.
+    fovs: Option<[Fovf; 2]>,
+    /// Eye poses, from the last call with valid poses.
+    eye_frames: Option<EyeFrames>,
+    /// Fixation point, from the last call with valid poses.
+    fixation: Option<Fixation>,
 }
 
 impl OpenXRLayer {
     pub fn new() -> OpenXRLayer {
-        let res = OpenXRLayer {
+        let config = Config::load();
+        let (source, controller) = source::from_config(&config);
+        let calibration = Calibration::new(&config);
+        let gaze_correction = calibration.correction();
+
+        OpenXRLayer {
             instance: None,
+            enabled_extensions: Vec::new(),
+            native_extensions: Vec::new(),
             get_instance_proc_addr: None,
             enumerate_instance_extensions_properties: None,
             get_system_properties: None,
             suggest_interaction_profile_bindings: None,
             path_to_string: None,
+            string_to_path: None,
+            create_reference_space: None,
             eye_gaze_action: None,
             create_action_space: None,
+            eye_gaze_space: None,
             l_eye_gaze_space: None,
             r_eye_gaze_space: None,
             get_action_state_pose: None,
+            get_action_state_boolean: None,
+            get_action_state_vector2f: None,
             locate_space: None,
             locate_views: None,
+            create_session: None,
+            destroy_session: None,
+            poll_event: None,
+            wait_frame: None,
             possible_spaces: HashMap::new(),
-            start_time: SystemTime::now(),
-            server: OSCServer::new(),
-        };
-
-        res.server.run();
-
-        res
+            face_trackers2: HashSet::new(),
+            facial_trackers_htc: HashSet::new(),
+            next_tracker_handle: 1,
+            sessions: Vec::new(),
+            located_views: Latch::new(LocatedViews::default()),
+            user_presence: Mutex::new(UserPresence::new(&config)),
+            pending_presence_events: Mutex::new(VecDeque::new()),
+            source,
+            controller,
+            frame_sampler: Mutex::new(FrameSampler {
+                pose_filter: GazeFilter::new(&config, "pose_filter"),
+                view_filter: GazeFilter::new(&config, "view_filter"),
+                predictor: Predictor::new(&config),
+                view_gaze: ViewGaze::new(&config),
+                gaze_event: None,
+                calibration,
+                recenter_hold: ChordHold::default(),
+            }),
+            gaze_correction: Latch::new(gaze_correction),
+            mounting: Mounting::new(&config),
+            pupil_offset: PupilOffset::new(&config),
+            eye_validity: EyeValidity::new(&config),
+            staleness: Latch::new(Staleness::new(&config)),
+            head_gaze_fallback: config.get("staleness.head_gaze_fallback", false),
+            view_space: None,
+            vergence: Vergence::new(&config),
+            recenter_chord: RecenterChord::new(&config),
+            thumbstick_actions: HashSet::new(),
+            thumbstick_click_actions: HashSet::new(),
+            thumbstick_hand: None,
+            classifier: GazeClassifier::new(&config),
+            osc_output: OscOutput::new(&config),
+            latched_frames: Latch::new(LatchedFrames::default()),
+        }
     }
 
     pub unsafe fn enumerate_instance_extension_properties(
@@ -84,45 +369,55 @@ impl OpenXRLayer {
         property_count_output: *mut u32,
         properties_ptr: *mut ExtensionProperties,
     ) -> Result {
-        let mut result = self.enumerate_instance_extensions_properties.unwrap()(
+        let mut extensions = match next_extensions(
+            self.enumerate_instance_extensions_properties.unwrap(),
             layer_name,
-            property_capacity_input,
-            property_count_output,
-            properties_ptr,
-        );
-
-        let base_offset = *property_count_output as usize;
-        *property_count_output += ADVERTISED_EXTENSIONS.len() as u32;
-        if property_capacity_input > 0 {
-            if property_capacity_input < *property_count_output {
-                result = Result::ERROR_SIZE_INSUFFICIENT;
-            } else {
-                result = Result::SUCCESS;
+        ) {
+            Ok(extensions) => extensions,
+            Err(result) => return result,
+        };
 
-                let properties = std::slice::from_raw_parts_mut(
-                    properties_ptr,
-                    (*property_count_output).try_into().unwrap(),
-                );
+        // Add ours, once if the runtime has some of them too.
+        let next_names: Vec<String> = extensions.iter().map(extension_name).collect();
+        for extension in ADVERTISED_EXTENSIONS {
+            if next_names.iter().any(|name| name == extension.name) {
+                continue;
+            }
 
-                for i in base_offset..*property_count_output as usize {
-                    if properties[i].ty != StructureType::EXTENSION_PROPERTIES {
-                        result = Result::ERROR_VALIDATION_FAILURE;
-                        break;
-                    }
+            let mut properties = ExtensionProperties {
+                ty: StructureType::EXTENSION_PROPERTIES,
+                next: std::ptr::null_mut(),
+                extension_name: [0; openxr_sys::MAX_EXTENSION_NAME_SIZE],
+                extension_version: extension.version,
+            };
+            for (dst, src) in properties
+                .extension_name
+                .iter_mut()
+                .zip(extension.name.bytes())
+            {
+                *dst = src as c_char;
+            }
+            extensions.push(properties);
+        }
 
-                    let extension = &ADVERTISED_EXTENSIONS[i - base_offset];
+        *property_count_output = extensions.len() as u32;
+        if property_capacity_input == 0 {
+            return Result::SUCCESS;
+        }
+        if property_capacity_input < *property_count_output {
+            return Result::ERROR_SIZE_INSUFFICIENT;
+        }
 
-                    std::ptr::copy(
-                        extension.name.as_ptr(),
-                        properties[i].extension_name.as_mut_ptr() as *mut u8,
-                        extension.name.len(),
-                    );
-                    properties[i].extension_version = extension.version;
-                }
+        let properties = std::slice::from_raw_parts_mut(properties_ptr, extensions.len());
+        for (properties, extension) in properties.iter_mut().zip(&extensions) {
+            if properties.ty != StructureType::EXTENSION_PROPERTIES {
+                return Result::ERROR_VALIDATION_FAILURE;
             }
+            properties.extension_name = extension.extension_name;
+            properties.extension_version = extension.extension_version;
         }
 
-        result
+        Result::SUCCESS
     }
 
     pub unsafe fn get_system_properties(
@@ -144,6 +439,28 @@ impl OpenXRLayer {
                 property.supports_eye_gaze_interaction = true.into();
             }
 
+            if property.ty.into_raw() == sys::TYPE_SYSTEM_FACE_TRACKING_PROPERTIES2_FB {
+                let property = &mut *(property_ptr as *mut sys::SystemFaceTrackingProperties2FB);
+                property.supports_visual_face_tracking = true.into();
+                property.supports_audio_face_tracking = false.into();
+            }
+
+            if property.ty == StructureType::SYSTEM_FACIAL_TRACKING_PROPERTIES_HTC {
+                let property = &mut *(property_ptr as *mut SystemFacialTrackingPropertiesHTC);
+                property.support_eye_facial_tracking = false.into();
+                property.support_lip_facial_tracking = true.into();
+            }
+
+            if property.ty == StructureType::SYSTEM_FOVEATION_EYE_TRACKED_PROPERTIES_META {
+                let property = &mut *(property_ptr as *mut SystemFoveationEyeTrackedPropertiesMETA);
+                property.supports_foveation_eye_tracked = true.into();
+            }
+
+            if property.ty.into_raw() == sys::TYPE_SYSTEM_USER_PRESENCE_PROPERTIES_EXT {
+                let property = &mut *(property_ptr as *mut sys::SystemUserPresencePropertiesEXT);
+                property.supports_user_presence = true.into();
+            }
+
             property_ptr = property.next;
         }
 
@@ -172,6 +489,21 @@ impl OpenXRLayer {
         );
 
         if interaction_profile != "/interaction_profiles/ext/eye_gaze_interaction" {
+            if self.recenter_chord.is_enabled() || self.controller.is_some() {
+                let bindings = std::slice::from_raw_parts(
+                    suggested_bindings.suggested_bindings,
+                    suggested_bindings
+                        .count_suggested_bindings
+                        .try_into()
+                        .unwrap(),
+                );
+                for binding in bindings {
+                    let path = self.path_to_string(binding.binding);
+                    self.recenter_chord.add_binding(binding.action, &path);
+                    self.add_thumbstick_binding(binding.action, &path);
+                }
+            }
+
             return self.suggest_interaction_profile_bindings.unwrap()(
                 instance,
                 suggested_bindings,
@@ -196,6 +528,13 @@ impl OpenXRLayer {
                     suggested_binding.action
                 );
 
+                if let Some(eye_gaze_space) = self
+                    .possible_spaces
+                    .get(&(suggested_binding.action, Path::NULL))
+                {
+                    self.eye_gaze_space = Some(*eye_gaze_space);
+                    println!("Combined eye gaze space found: {:?}", eye_gaze_space);
+                }
                 if let Some(l_eye_gaze_space) = self
                     .possible_spaces
                     // TODO: Don't hardcode "/user/hand/left" as Path(1)
@@ -262,17 +601,98 @@ impl OpenXRLayer {
 
         // println!("--> get_action_state_pose {:?}", (*get_info).subaction_path);
 
-        let eye_gaze_data = self.server.eye_gaze_data.lock().unwrap();
+        let eye_gaze_data = self.frame_gaze(None).pose;
         let state = &mut *state;
 
-        // Report tracking as disabled if there is no data incoming.
-        state.is_active =
-            (eye_gaze_data.time.elapsed().unwrap() < Duration::from_millis(50)).into();
+        // Report tracking as disabled if there is no data incoming, unless the head stands in.
+        state.is_active = (self.staleness.read().is_eye_fresh(&eye_gaze_data)
+            || (self.head_gaze_fallback && self.view_space.is_some()))
+        .into();
 
         // println!("<-- get_action_state_pose");
         Result::SUCCESS
     }
 
+    pub unsafe fn get_action_state_boolean(
+        &self,
+        session: Session,
+        get_info: *const ActionStateGetInfo,
+        state: *mut ActionStateBoolean,
+    ) -> Result {
+        let res = self.get_action_state_boolean.unwrap()(session, get_info, state);
+        if res != Result::SUCCESS {
+            return res;
+        }
+
+        let state = &*state;
+        let is_pressed = state.is_active.into() && state.current_state.into();
+        self.recenter_chord
+            .set_pressed((*get_info).action, is_pressed);
+
+        let get_info = &*get_info;
+        if let Some(controller) = &self.controller {
+            if self.thumbstick_click_actions.contains(&get_info.action)
+                && self.is_thumbstick_hand(get_info.subaction_path)
+            {
+                controller.update_thumbstick_click(is_pressed);
+            }
+        }
+
+        res
+    }
+
+    pub unsafe fn get_action_state_vector2f(
+        &self,
+        session: Session,
+        get_info: *const ActionStateGetInfo,
+        state: *mut ActionStateVector2f,
+    ) -> Result {
+        let res = self.get_action_state_vector2f.unwrap()(session, get_info, state);
+        if res != Result::SUCCESS {
+            return res;
+        }
+
+        let (get_info, state) = (&*get_info, &*state);
+        if let Some(controller) = &self.controller {
+            if self.thumbstick_actions.contains(&get_info.action)
+                && self.is_thumbstick_hand(get_info.subaction_path)
+                && state.is_active.into()
+            {
+                let position = state.current_state;
+                controller.update_thumbstick(position.x, position.y);
+            }
+        }
+
+        res
+    }
+
+    /// Remember `action` if the app suggested it for the thumbstick of `controller`.
+    unsafe fn add_thumbstick_binding(&mut self, action: Action, binding: &str) {
+        let Some(controller) = &self.controller else {
+            return;
+        };
+        let thumbstick = controller.binding();
+        if binding == thumbstick {
+            println!("Gaze thumbstick {binding} bound to {:?}", action);
+            self.thumbstick_actions.insert(action);
+        } else if binding.strip_prefix(thumbstick) == Some("/click") {
+            println!("Gaze thumbstick click {binding} bound to {:?}", action);
+            self.thumbstick_click_actions.insert(action);
+        } else {
+            return;
+        }
+
+        if self.thumbstick_hand.is_none() {
+            self.thumbstick_hand = self.string_to_path(controller.hand());
+        }
+    }
+
+    /// Whether an action state queried for `subaction_path` comes from the hand of the
+    /// thumbstick.
+    fn is_thumbstick_hand(&self, subaction_path: Path) -> bool {
+        source::is_thumbstick_hand(subaction_path, self.thumbstick_hand)
+    }
+
     pub unsafe fn locate_space(
         &self,
         space: Space,
@@ -284,8 +704,9 @@ impl OpenXRLayer {
 
         let is_left = self.l_eye_gaze_space.is_some_and(|s| s == space);
         let is_right = self.r_eye_gaze_space.is_some_and(|s| s == space);
+        let is_combined = self.eye_gaze_space.is_some_and(|s| s == space);
 
-        if !is_left && !is_right {
+        if !is_left && !is_right && !is_combined {
             return self.locate_space.unwrap()(space, base_space, time, location);
         }
 
@@ -293,45 +714,79 @@ impl OpenXRLayer {
 
         let location = &mut *location;
 
-        location.location_flags |= SpaceLocationFlags::POSITION_TRACKED;
-        location.location_flags |= SpaceLocationFlags::ORIENTATION_TRACKED;
+        let FrameGaze {
+            pose: eye_gaze_data,
+            valid,
+            ..
+        } = self.frame_gaze(Some(time));
 
-        let eye_gaze_data = self.server.eye_gaze_data.lock().unwrap();
+        let eye_q = |is_left: bool| {
+            let (direction, torsion) = if is_left {
+                (eye_gaze_data.l_direction, eye_gaze_data.l_torsion)
+            } else {
+                (eye_gaze_data.r_direction, eye_gaze_data.r_torsion)
+            };
+            pose::look_rotation(direction, torsion.unwrap_or(0.0))
+        };
 
-        let (pitch, yaw) = if is_left {
-            (eye_gaze_data.l_pitch, eye_gaze_data.l_yaw)
+        // A lost eye still gets a valid orientation from the other one, but isn't tracked.
+        let (q, is_tracked, sample_time) = if is_combined {
+            let sample_time = match valid {
+                [true, false] => eye_gaze_data.l_time,
+                [false, true] => eye_gaze_data.r_time,
+                _ => eye_gaze_data.l_time.max(eye_gaze_data.r_time),
+            };
+            (
+                self.eye_validity
+                    .combine([eye_q(true), eye_q(false)], valid),
+                valid[0] || valid[1],
+                sample_time,
+            )
+        } else if is_left {
+            (eye_q(true), valid[0], eye_gaze_data.l_time)
         } else {
-            (eye_gaze_data.r_pitch, eye_gaze_data.r_yaw)
+            (eye_q(false), valid[1], eye_gaze_data.r_time)
         };
 
-        use quaternion_core as quat;
-        let q = quat::from_euler_angles(
-            quat::RotationType::Extrinsic,
-            quat::RotationSequence::XYZ,
-            [pitch, yaw, 0.0],
-        );
+        // Without fresh data the last pose isn't reported at all, or the head is.
+        let is_fresh = self.staleness.read().is_eye_fresh(&eye_gaze_data);
+        if let Some(view_space) = self
+            .view_space
+            .filter(|_| self.head_gaze_fallback && !is_fresh)
+        {
+            return self.locate_head_gaze(view_space, base_space, time, location);
+        }
+        location.location_flags = SpaceLocationFlags::EMPTY;
+        if is_fresh {
+            location.location_flags |= SpaceLocationFlags::POSITION_VALID
+                | SpaceLocationFlags::POSITION_TRACKED
+                | SpaceLocationFlags::ORIENTATION_VALID;
+        }
+        if is_fresh && is_tracked {
+            location.location_flags |= SpaceLocationFlags::ORIENTATION_TRACKED;
+        }
 
-        // TODO: Figure out if this is correct position.
-        // If eyeball position is required, can use `xrLocateView` to query camera position.
-        location.pose.position = Vector3f {
-            x: 0.0,
-            y: 0.0,
-            z: 0.0,
-        };
-        location.pose.orientation = Quaternionf {
-            w: q.0,
-            x: q.1[0],
-            y: q.1[1],
-            z: q.1[2],
+        // The fixation point is relative to the head, so a gaze from it is placed in the base
+        // space with the head at the time of the query, position and orientation alike.
+        let head = self
+            .vergence
+            .gaze_origin(self.located_views.read().fixation.as_ref())
+            .filter(|_| is_combined)
+            .zip(self.view_space)
+            .and_then(|(origin, view_space)| {
+                Some((origin, self.locate_head(view_space, base_space, time)?))
+            });
+        location.pose = match head {
+            Some((origin, head)) => pose::compose(&head, origin, q),
+            None => Posef {
+                orientation: pose::to_quaternionf(q),
+                position: pose::to_vector3f([0.0; 3]),
+            },
         };
 
         // println!("locate_space {:?}", location);
 
-        if !location.next.is_null() {
-            let eye_gaze_sample_time = &mut *(location.next as *mut EyeGazeSampleTimeEXT);
-            eye_gaze_sample_time.time = Time::from_nanos(0);
-            // println!("locate_space {:?}", eye_gaze_sample_time);
-        }
+        set_eye_gaze_sample_time(location, time::from_sample_time(sample_time));
 
         Result::SUCCESS
     }
@@ -358,61 +813,609 @@ impl OpenXRLayer {
             return res;
         }
 
-        if (*view_locate_info).view_configuration_type != ViewConfigurationType::PRIMARY_STEREO {
+        let Some(layout) = pupil_offset::view_layout((*view_locate_info).view_configuration_type)
+        else {
+            return Result::SUCCESS;
+        };
+        // A capacity of 0 only asks for the view count.
+        if (view_capacity_input as usize) < layout.len() || *view_count_output < layout.len() as u32
+        {
             return Result::SUCCESS;
         }
 
-        let views = std::slice::from_raw_parts_mut(views, (*view_count_output).try_into().unwrap());
+        let views = std::slice::from_raw_parts_mut(views, layout.len());
+        let view_state = &*view_state;
+
+        let FrameGaze {
+            view: eye_gaze_data,
+            pupil_directions: [l_direction, r_direction],
+            ..
+        } = self.frame_gaze(Some((*view_locate_info).display_time));
+
+        let eye_frames =
+            pupil_offset::are_poses_valid(view_state).then(|| EyeFrames::from_views(views));
+        self.located_views.update(|located_views| {
+            located_views.fovs = Some([views[0].fov, views[1].fov]);
+            if let Some(eye_frames) = eye_frames {
+                located_views.eye_frames = Some(eye_frames);
+                located_views.fixation = Some(self.vergence.estimate(&eye_frames, &eye_gaze_data));
+            }
+        });
+
+        if !self.pupil_offset.is_enabled(view_state) {
+            return Result::SUCCESS;
+        }
 
         let apply_pupil_offset = |view: &mut View, is_left: bool| {
-            use quat::QuaternionOps;
-            use quaternion_core as quat;
+            let (direction, torsion) = if is_left {
+                (l_direction, eye_gaze_data.l_torsion)
+            } else {
+                (r_direction, eye_gaze_data.r_torsion)
+            };
+            let gaze_q = pose::look_rotation(direction, torsion.unwrap_or(0.0));
+            self.pupil_offset.apply(&mut view.pose, gaze_q, is_left);
+        };
 
-            const EYEBALL_RADIUS: f32 = 12.0 * 0.1;
+        for (view, &is_left) in views.iter_mut().zip(layout) {
+            apply_pupil_offset(view, is_left);
+        }
 
-            let pos = view.pose.position;
-            let mut pos = [pos.x, pos.y, pos.z];
+        Result::SUCCESS
+    }
 
-            let quat = view.pose.orientation;
-            let fwd_q = (quat.w, [quat.x, quat.y, quat.z]);
+    pub unsafe fn wait_frame(
+        &self,
+        session: Session,
+        frame_wait_info: *const FrameWaitInfo,
+        frame_state: *mut FrameState,
+    ) -> Result {
+        let result = self.wait_frame.unwrap()(session, frame_wait_info, frame_state);
+        if result != Result::SUCCESS {
+            return result;
+        }
 
-            let mut fwd_v = quat::to_rotation_vector(fwd_q);
-            fwd_v = quat::normalize(fwd_v);
+        // Engines locate the views before beginning the frame, so the gaze is latched as soon as
+        // the display time is known.
+        let display_time = (*frame_state).predicted_display_time;
+        let frame_gaze = self.sample_gaze(&mut self.frame_sampler.lock().unwrap(), display_time);
+        self.latched_frames.update(|frames| frames.push(frame_gaze));
 
-            pos = pos.sub(fwd_v.scale(EYEBALL_RADIUS));
+        result
+    }
 
-            let eye_gaze_data = self.server.eye_gaze_data.lock().unwrap();
+    /// The gaze latched for the frame displayed nearest to `time`, or for the newest frame.
+    /// Without a frame loop the gaze is sampled for the query itself, smoothed and predicted
+    /// the same way.
+    fn frame_gaze(&self, time: Option<Time>) -> FrameGaze {
+        if let Some(frame_gaze) = self.latched_frames.read().nearest(time, Instant::now()) {
+            return frame_gaze;
+        }
 
-            let (pitch, yaw) = if is_left {
-                (eye_gaze_data.l_pitch, eye_gaze_data.l_yaw)
-            } else {
-                (eye_gaze_data.r_pitch, eye_gaze_data.r_yaw)
-            };
+        let mut sampler = self.frame_sampler.lock().unwrap();
+        self.sample_gaze(&mut sampler, time.unwrap_or_else(time::now))
+    }
+
+    /// Sample, smooth and predict the gaze for the frame displayed at `display_time`.
+    fn sample_gaze(&self, sampler: &mut FrameSampler, display_time: Time) -> FrameGaze {
+        // Everything below works from one copy of the history, in the headset.
+        let history = self.headset_gaze_history();
+        let correction = self.handle_commands(sampler, &history);
+        self.staleness.update(|s| s.update(&history));
+
+        let latest = correction.apply(self.mounting.apply(self.source.eye_gaze_data()));
+        let history: Vec<EyeGazeData> = history.iter().map(|s| correction.apply(*s)).collect();
+        let (event, angular_velocity) = self.classifier.classify(&history);
+        let prediction =
+            sampler
+                .predictor
+                .predict(&history, event, time::to_system_time(display_time));
+
+        let (pose, valid) = self
+            .eye_validity
+            .with_fallback(prediction.apply(sampler.pose_filter.update(latest, &history)));
+        let (view, _) = self
+            .eye_validity
+            .with_fallback(prediction.apply(sampler.view_filter.update(latest, &history)));
+
+        if sampler.gaze_event != Some(event) {
+            sampler.gaze_event = Some(event);
+            if let Some(osc_output) = &self.osc_output {
+                osc_output.send_gaze_event(event, angular_velocity);
+            }
+        }
+
+        FrameGaze {
+            display_time: Some(display_time),
+            latched_at: Instant::now(),
+            pose,
+            view,
+            pupil_directions: sampler
+                .view_gaze
+                .update(event, [view.l_direction, view.r_direction]),
+            valid,
+        }
+    }
+
+    /// The gaze history of the source rotated into the headset, oldest first.
+    fn headset_gaze_history(&self) -> Vec<EyeGazeData> {
+        self.source
+            .eye_gaze_history()
+            .iter()
+            .map(|sample| self.mounting.apply(*sample))
+            .collect()
+    }
+
+    /// Handle the control commands received by the source and the recenter chord since the
+    /// last frame, `history` is the uncalibrated gaze in the headset. Returns the correction to
+    /// apply, published to the other queries if it changed.
+    fn handle_commands(
+        &self,
+        sampler: &mut FrameSampler,
+        history: &[EyeGazeData],
+    ) -> GazeCorrection {
+        let mut commands = self.source.take_commands();
+        if self
+            .recenter_chord
+            .update(&mut sampler.recenter_hold, Instant::now())
+        {
+            commands.push(Command::Recenter);
+        }
+        for command in &commands {
+            sampler.calibration.handle_command(command, history);
+        }
 
-            let gaze_q = quat::from_euler_angles(
-                quat::RotationType::Extrinsic,
-                quat::RotationSequence::XYZ,
-                [pitch, yaw, 0.0],
+        let correction = sampler.calibration.correction();
+        if !commands.is_empty() {
+            self.gaze_correction.update(|c| *c = correction);
+        }
+        correction
+    }
+
+    pub unsafe fn create_face_tracker2_fb(
+        &mut self,
+        _session: Session,
+        create_info: *const sys::FaceTrackerCreateInfo2FB,
+        face_tracker: *mut FaceTracker2FB,
+    ) -> Result {
+        let create_info = &*create_info;
+        println!("--> create_face_tracker2_fb {:?}", create_info);
+
+        if create_info.ty.into_raw() != sys::TYPE_FACE_TRACKER_CREATE_INFO2_FB {
+            return Result::ERROR_VALIDATION_FAILURE;
+        }
+        if create_info.face_expression_set != sys::FACE_EXPRESSION_SET2_DEFAULT_FB {
+            return Result::ERROR_VALIDATION_FAILURE;
+        }
+
+        // Only visual data is available, refuse if the app accepts audio only.
+        if create_info.requested_data_source_count > 0 {
+            let data_sources = std::slice::from_raw_parts(
+                create_info.requested_data_sources,
+                create_info.requested_data_source_count.try_into().unwrap(),
             );
+            if !data_sources.contains(&sys::FACE_TRACKING_DATA_SOURCE2_VISUAL_FB) {
+                return Result::ERROR_FEATURE_UNSUPPORTED;
+            }
+        }
 
-            let gaze_fwd_q = quat::mul(fwd_q, gaze_q);
-            let gaze_fwd_v = quat::normalize(quat::to_rotation_vector(gaze_fwd_q));
+        let handle = FaceTracker2FB::from_raw(self.next_tracker_handle);
+        self.next_tracker_handle += 1;
+        self.face_trackers2.insert(handle);
+        *face_tracker = handle;
 
-            pos = pos.add(gaze_fwd_v.scale(EYEBALL_RADIUS));
+        println!("<-- create_face_tracker2_fb {:?}", handle);
+        Result::SUCCESS
+    }
+
+    pub unsafe fn destroy_face_tracker2_fb(&mut self, face_tracker: FaceTracker2FB) -> Result {
+        println!("destroy_face_tracker2_fb {:?}", face_tracker);
+        if !self.face_trackers2.remove(&face_tracker) {
+            return Result::ERROR_HANDLE_INVALID;
+        }
+        Result::SUCCESS
+    }
+
+    pub unsafe fn get_face_expression_weights2_fb(
+        &self,
+        face_tracker: FaceTracker2FB,
+        expression_info: *const sys::FaceExpressionInfo2FB,
+        expression_weights: *mut sys::FaceExpressionWeights2FB,
+    ) -> Result {
+        if !self.face_trackers2.contains(&face_tracker) {
+            return Result::ERROR_HANDLE_INVALID;
+        }
+
+        let expression_info = &*expression_info;
+        let expression_weights = &mut *expression_weights;
+
+        if expression_info.ty.into_raw() != sys::TYPE_FACE_EXPRESSION_INFO2_FB
+            || expression_weights.ty.into_raw() != sys::TYPE_FACE_EXPRESSION_WEIGHTS2_FB
+            || expression_weights.weight_count as usize != sys::FACE_EXPRESSION2_COUNT_FB
+            || expression_weights.confidence_count as usize != sys::FACE_CONFIDENCE2_COUNT_FB
+        {
+            return Result::ERROR_VALIDATION_FAILURE;
+        }
 
-            view.pose.position = Vector3f {
-                x: pos[0],
-                y: pos[1],
-                z: pos[2],
+        let weights = std::slice::from_raw_parts_mut(
+            expression_weights.weights,
+            sys::FACE_EXPRESSION2_COUNT_FB,
+        );
+        let confidences = std::slice::from_raw_parts_mut(
+            expression_weights.confidences,
+            sys::FACE_CONFIDENCE2_COUNT_FB,
+        );
+
+        let FrameGaze {
+            pose: eye_gaze_data,
+            valid,
+            ..
+        } = self.frame_gaze(None);
+        let mouth_data = self.source.mouth_data();
+        let staleness = self.staleness.read();
+        let is_eye_valid = staleness.is_eye_fresh(&eye_gaze_data);
+        let is_mouth_valid = staleness.is_mouth_fresh(&mouth_data);
+
+        // Regions without fresh data are reported with no confidence.
+        weights.fill(0.0);
+        face::fill_eye_expressions2(weights, &eye_gaze_data);
+        if is_mouth_valid {
+            face::fill_mouth_expressions2(weights, &mouth_data);
+        }
+        confidences[sys::FACE_CONFIDENCE2_LOWER_FACE_FB] = if is_mouth_valid { 1.0 } else { 0.0 };
+        confidences[sys::FACE_CONFIDENCE2_UPPER_FACE_FB] = if is_eye_valid { 1.0 } else { 0.0 };
+
+        expression_weights.is_valid = (is_eye_valid || is_mouth_valid).into();
+        expression_weights.is_eye_following_blendshapes_valid =
+            (is_eye_valid && (valid[0] || valid[1])).into();
+        expression_weights.data_source = sys::FACE_TRACKING_DATA_SOURCE2_VISUAL_FB;
+        expression_weights.time = expression_info.time;
+
+        Result::SUCCESS
+    }
+
+    pub unsafe fn create_facial_tracker_htc(
+        &mut self,
+        _session: Session,
+        create_info: *const FacialTrackerCreateInfoHTC,
+        facial_tracker: *mut FacialTrackerHTC,
+    ) -> Result {
+        let create_info = &*create_info;
+        println!("--> create_facial_tracker_htc {:?}", create_info);
+
+        if create_info.ty != StructureType::FACIAL_TRACKER_CREATE_INFO_HTC {
+            return Result::ERROR_VALIDATION_FAILURE;
+        }
+        // Eye expressions aren't advertised in the system properties.
+        if create_info.facial_tracking_type != FacialTrackingTypeHTC::LIP_DEFAULT {
+            return Result::ERROR_FEATURE_UNSUPPORTED;
+        }
+
+        let handle = self.next_tracker_handle;
+        self.next_tracker_handle += 1;
+        self.facial_trackers_htc.insert(handle);
+        *facial_tracker = FacialTrackerHTC::from_raw(handle);
+
+        println!("<-- create_facial_tracker_htc {:?}", handle);
+        Result::SUCCESS
+    }
+
+    pub unsafe fn destroy_facial_tracker_htc(
+        &mut self,
+        facial_tracker: FacialTrackerHTC,
+    ) -> Result {
+        println!("destroy_facial_tracker_htc {:?}", facial_tracker);
+        if !self.facial_trackers_htc.remove(&facial_tracker.into_raw()) {
+            return Result::ERROR_HANDLE_INVALID;
+        }
+        Result::SUCCESS
+    }
+
+    pub unsafe fn get_facial_expressions_htc(
+        &self,
+        facial_tracker: FacialTrackerHTC,
+        facial_expressions: *mut FacialExpressionsHTC,
+    ) -> Result {
+        if !self
+            .facial_trackers_htc
+            .contains(&facial_tracker.into_raw())
+        {
+            return Result::ERROR_HANDLE_INVALID;
+        }
+
+        let facial_expressions = &mut *facial_expressions;
+        if facial_expressions.ty != StructureType::FACIAL_EXPRESSIONS_HTC
+            || (facial_expressions.expression_count as usize) < FACIAL_EXPRESSION_LIP_COUNT_HTC
+        {
+            return Result::ERROR_VALIDATION_FAILURE;
+        }
+
+        let weights = std::slice::from_raw_parts_mut(
+            facial_expressions.expression_weightings,
+            FACIAL_EXPRESSION_LIP_COUNT_HTC,
+        );
+
+        let mouth_data = self.source.mouth_data();
+        let is_active = self.staleness.read().is_mouth_fresh(&mouth_data);
+
+        weights.fill(0.0);
+        if is_active {
+            face::fill_lip_expressions_htc(weights, &mouth_data);
+        }
+
+        facial_expressions.is_active = is_active.into();
+        facial_expressions.sample_time = time::from_sample_time(mouth_data.time);
+
+        Result::SUCCESS
+    }
+
+    pub unsafe fn create_session(
+        &mut self,
+        instance: Instance,
+        create_info: *const SessionCreateInfo,
+        session: *mut Session,
+    ) -> Result {
+        let result = self.create_session.unwrap()(instance, create_info, session);
+        if result != Result::SUCCESS {
+            return result;
+        }
+
+        println!("create_session {:?}", *session);
+        self.sessions.push(*session);
+
+        self.view_space = self.create_view_space(*session);
+
+        // Let the app know the initial state.
+        let is_present = self.user_presence.get_mut().unwrap().is_present();
+        self.pending_presence_events
+            .get_mut()
+            .unwrap()
+            .push_back((*session, is_present));
+
+        Result::SUCCESS
+    }
+
+    pub unsafe fn destroy_session(&mut self, session: Session) -> Result {
+        println!("destroy_session {:?}", session);
+        self.sessions.retain(|s| *s != session);
+        self.pending_presence_events
+            .get_mut()
+            .unwrap()
+            .retain(|(s, _)| *s != session);
+        self.latched_frames
+            .update(|frames| *frames = LatchedFrames::default());
+        // The runtime destroys the spaces of the session with it.
+        self.view_space = None;
+
+        self.destroy_session.unwrap()(session)
+    }
+
+    pub unsafe fn poll_event(
+        &self,
+        instance: Instance,
+        event_data: *mut EventDataBuffer,
+    ) -> Result {
+        let result = self.poll_event.unwrap()(instance, event_data);
+
+        // Runtime events go first, ours are delivered when its queue is empty. A runtime with its
+        // own user presence sends all of them.
+        if result != Result::EVENT_UNAVAILABLE
+            || !self.is_extension_enabled("XR_EXT_user_presence")
+            || self.is_native_extension("XR_EXT_user_presence")
+        {
+            return result;
+        }
+
+        let mut pending_presence_events = self.pending_presence_events.lock().unwrap();
+        let presence_change = self
+            .user_presence
+            .lock()
+            .unwrap()
+            .update(&self.source.eye_gaze_data());
+        if let Some(is_present) = presence_change {
+            println!("User presence changed: {is_present}");
+            for session in &self.sessions {
+                pending_presence_events.push_back((*session, is_present));
             }
+        }
+
+        let Some((session, is_user_present)) = pending_presence_events.pop_front() else {
+            return result;
         };
 
-        apply_pupil_offset(&mut views[0], true);
-        apply_pupil_offset(&mut views[1], false);
+        let event = &mut *(event_data as *mut sys::EventDataUserPresenceChangedEXT);
+        event.ty = StructureType::from_raw(sys::TYPE_EVENT_DATA_USER_PRESENCE_CHANGED_EXT);
+        event.next = std::ptr::null();
+        event.session = session;
+        event.is_user_present = is_user_present.into();
 
         Result::SUCCESS
     }
 
+    pub unsafe fn get_foveation_eye_tracked_state_meta(
+        &self,
+        _session: Session,
+        foveation_state: *mut FoveationEyeTrackedStateMETA,
+    ) -> Result {
+        let foveation_state = &mut *foveation_state;
+        if foveation_state.ty != StructureType::FOVEATION_EYE_TRACKED_STATE_META {
+            return Result::ERROR_VALIDATION_FAILURE;
+        }
+
+        let FrameGaze {
+            view: eye_gaze_data,
+            valid,
+            ..
+        } = self.frame_gaze(None);
+        let is_valid = self.staleness.read().is_eye_fresh(&eye_gaze_data) && (valid[0] || valid[1]);
+
+        // Without fresh data and located views there's nothing to project.
+        let Some(view_fovs) = self.located_views.read().fovs.filter(|_| is_valid) else {
+            foveation_state.foveation_center = [Vector2f { x: 0.0, y: 0.0 }; 2];
+            foveation_state.flags = FoveationEyeTrackedStateFlagsMETA::EMPTY;
+            return Result::SUCCESS;
+        };
+
+        foveation_state.foveation_center = [
+            foveation::foveation_center(eye_gaze_data.l_direction, &view_fovs[0]),
+            foveation::foveation_center(eye_gaze_data.r_direction, &view_fovs[1]),
+        ];
+        foveation_state.flags = FoveationEyeTrackedStateFlagsMETA::VALID;
+
+        Result::SUCCESS
+    }
+
+    pub unsafe fn get_raw_eye_data_etvr(
+        &self,
+        _session: Session,
+        get_info: *const RawEyeDataGetInfoETVR,
+        eye_data: *mut RawEyeDataETVR,
+    ) -> Result {
+        let get_info = &*get_info;
+        let eye_data = &mut *eye_data;
+        if get_info.ty.into_raw() != raw_eye_data::TYPE_RAW_EYE_DATA_GET_INFO_ETVR
+            || eye_data.ty.into_raw() != raw_eye_data::TYPE_RAW_EYE_DATA_ETVR
+        {
+            return Result::ERROR_VALIDATION_FAILURE;
+        }
+
+        let requested_time = time::to_system_time(get_info.time);
+        let distance = |sample: &&EyeGazeData| match sample.time.duration_since(requested_time) {
+            Ok(d) => d,
+            Err(e) => e.duration(),
+        };
+
+        let history = self.source.eye_gaze_history();
+        let Some((index, sample)) = history.iter().enumerate().min_by_key(|(_, s)| distance(s))
+        else {
+            eye_data.sample_time = Time::from_nanos(0);
+            eye_data.sender_time = Time::from_nanos(0);
+            eye_data.eyes = [RawEyeStateETVR::default(); raw_eye_data::RAW_EYE_COUNT_ETVR];
+            return Result::SUCCESS;
+        };
+
+        eye_data.sample_time = time::from_system_time(sample.time);
+        eye_data.sender_time = sample
+            .sender_time
+            .map_or(Time::from_nanos(0), time::from_system_time);
+        let valid = self.eye_validity.validity(sample);
+        let pupil_valid = self.eye_validity.pupil_validity(sample);
+        eye_data.eyes[raw_eye_data::RAW_EYE_LEFT_ETVR] =
+            raw_eye_state(sample, true, valid[0], pupil_valid[0]);
+        eye_data.eyes[raw_eye_data::RAW_EYE_RIGHT_ETVR] =
+            raw_eye_state(sample, false, valid[1], pupil_valid[1]);
+
+        let mut next = eye_data.next as *mut BaseOutStructure;
+        while !next.is_null() {
+            if (*next).ty.into_raw() == raw_eye_data::TYPE_GAZE_CLASSIFICATION_ETVR {
+                let classification = &mut *(next as *mut GazeClassificationETVR);
+                let (event, angular_velocity) = self.classifier.classify(&history[..=index]);
+                classification.event = event as i32;
+                classification.angular_velocity = angular_velocity;
+            }
+            if (*next).ty.into_raw() == raw_eye_data::TYPE_FIXATION_POINT_ETVR {
+                let fixation_point = &mut *(next as *mut FixationPointETVR);
+                let fixation = self.located_views.read().eye_frames.map(|frames| {
+                    let sample = self
+                        .gaze_correction
+                        .read()
+                        .apply(self.mounting.apply(*sample));
+                    self.vergence.estimate(&frames, &sample)
+                });
+                let position = fixation.map_or([0.0; 3], |f| f.position);
+                fixation_point.position = pose::to_vector3f(position);
+                fixation_point.distance = fixation.map_or(0.0, |f| f.distance);
+                fixation_point.confidence = fixation.map_or(0.0, |f| f.confidence);
+            }
+            if (*next).ty.into_raw() == raw_eye_data::TYPE_EYE_TORSION_ETVR {
+                let eye_torsion = &mut *(next as *mut EyeTorsionETVR);
+                for (i, torsion) in [sample.l_torsion, sample.r_torsion].into_iter().enumerate() {
+                    eye_torsion.torsion[i] = torsion.unwrap_or(0.0);
+                    eye_torsion.is_torsion_valid[i] = (torsion.is_some() && valid[i]).into();
+                }
+            }
+            next = (*next).next;
+        }
+
+        Result::SUCCESS
+    }
+
+    pub fn is_extension_enabled(&self, name: &str) -> bool {
+        self.enabled_extensions.iter().any(|e| e == name)
+    }
+
+    pub fn is_native_extension(&self, name: &str) -> bool {
+        self.native_extensions.iter().any(|e| e == name)
+    }
+
+    /// Locate the head as the gaze, looking straight ahead from the view origin, with an
+    /// untracked orientation.
+    unsafe fn locate_head_gaze(
+        &self,
+        view_space: Space,
+        base_space: Space,
+        time: Time,
+        location: &mut SpaceLocation,
+    ) -> Result {
+        // The runtime fills in what it knows of the chain, like `XrSpaceVelocity`, and skips
+        // the eye gaze sample time.
+        let result = self.locate_space.unwrap()(view_space, base_space, time, location);
+        if result != Result::SUCCESS {
+            return result;
+        }
+
+        location.location_flags &= !SpaceLocationFlags::ORIENTATION_TRACKED;
+        set_eye_gaze_sample_time(location, Time::from_nanos(0));
+
+        Result::SUCCESS
+    }
+
+    /// The head located in `base_space` at `time`, `None` if it can't be located.
+    unsafe fn locate_head(
+        &self,
+        view_space: Space,
+        base_space: Space,
+        time: Time,
+    ) -> Option<Posef> {
+        let mut location = SpaceLocation {
+            ty: SpaceLocation::TYPE,
+            next: std::ptr::null_mut(),
+            location_flags: SpaceLocationFlags::EMPTY,
+            pose: Posef::IDENTITY,
+        };
+        let result = self.locate_space.unwrap()(view_space, base_space, time, &mut location);
+        let valid = SpaceLocationFlags::POSITION_VALID | SpaceLocationFlags::ORIENTATION_VALID;
+        if result != Result::SUCCESS || !location.location_flags.contains(valid) {
+            return None;
+        }
+
+        Some(location.pose)
+    }
+
+    /// Create a VIEW reference space in `session`, `None` if the runtime refuses.
+    unsafe fn create_view_space(&self, session: Session) -> Option<Space> {
+        let create_info = ReferenceSpaceCreateInfo {
+            ty: ReferenceSpaceCreateInfo::TYPE,
+            next: std::ptr::null(),
+            reference_space_type: ReferenceSpaceType::VIEW,
+            pose_in_reference_space: Posef::IDENTITY,
+        };
+        let mut space = Space::NULL;
+        let result = self.create_reference_space?(session, &create_info, &mut space);
+        if result != Result::SUCCESS {
+            println!("Can't create the view space to locate the head: {result:?}");
+            return None;
+        }
+        Some(space)
+    }
+
+    unsafe fn string_to_path(&self, path: &str) -> Option<Path> {
+        let path = CString::new(path).ok()?;
+        let mut out = Path::NULL;
+        let result =
+            self.string_to_path?(self.instance.unwrap(), path.as_ptr(), &mut out as *mut Path);
+        (result == Result::SUCCESS).then_some(out)
+    }
+
     pub unsafe fn path_to_string(&self, path: Path) -> String {
         let mut buffer = vec![0u8; 128];
         let mut out_size = 0u32;
@@ -421,7 +1424,7 @@ impl OpenXRLayer {
             path,
             buffer.len().try_into().unwrap(),
             &mut out_size as *mut u32,
-            buffer.as_mut_ptr() as *mut u8,
+            buffer.as_mut_ptr() as *mut c_char,
         );
 
         CStr::from_bytes_until_nul(&buffer[..out_size as usize])
@@ -431,3 +1434,174 @@ impl OpenXRLayer {
             .to_string()
     }
 }
+
+fn raw_eye_state(
+    sample: &EyeGazeData,
+    is_left: bool,
+    is_gaze_valid: bool,
+    is_pupil_valid: bool,
+) -> RawEyeStateETVR {
+    let (direction, openness, widen, squeeze, pupil_diameter, confidence) = if is_left {
+        (
+            sample.l_direction,
+            sample.l_openness,
+            sample.l_widen,
+            sample.l_squeeze,
+            sample.l_pupil_diameter,
+            sample.l_confidence,
+        )
+    } else {
+        (
+            sample.r_direction,
+            sample.r_openness,
+            sample.r_widen,
+            sample.r_squeeze,
+            sample.r_pupil_diameter,
+            sample.r_confidence,
+        )
+    };
+
+    let (pitch, yaw) = pose::angles_from_direction(direction);
+
+    let mut flags = raw_eye_data::RAW_EYE_STATE_OPENNESS_VALID_BIT_ETVR;
+    if is_gaze_valid {
+        flags |= raw_eye_data::RAW_EYE_STATE_GAZE_VALID_BIT_ETVR;
+    }
+    if is_pupil_valid {
+        flags |= raw_eye_data::RAW_EYE_STATE_PUPIL_DIAMETER_VALID_BIT_ETVR;
+    }
+    if confidence.is_some() {
+        flags |= raw_eye_data::RAW_EYE_STATE_CONFIDENCE_VALID_BIT_ETVR;
+    }
+
+    RawEyeStateETVR {
+        flags,
+        pitch,
+        yaw,
+        openness,
+        widen,
+        squeeze,
+        pupil_diameter: pupil_diameter.filter(|_| is_pupil_valid).unwrap_or(0.0),
+        confidence: confidence.unwrap_or(0.0),
+    }
+}
+
+/// Write `time` to the `XrEyeGazeSampleTimeEXT` in the `next` chain of `location`, if any.
+unsafe fn set_eye_gaze_sample_time(location: &mut SpaceLocation, time: Time) {
+    let mut next = location.next as *mut BaseOutStructure;
+    while !next.is_null() {
+        if (*next).ty == StructureType::EYE_GAZE_SAMPLE_TIME_EXT {
+            let eye_gaze_sample_time = &mut *(next as *mut EyeGazeSampleTimeEXT);
+            eye_gaze_sample_time.time = time;
+        }
+        next = (*next).next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(display_nanos: i64, latched_at: Instant) -> FrameGaze {
+        FrameGaze {
+            display_time: Some(Time::from_nanos(display_nanos)),
+            latched_at,
+            pose: EyeGazeData::default(),
+            view: EyeGazeData::default(),
+            pupil_directions: [pose::FORWARD; 2],
+            valid: [true; 2],
+        }
+    }
+
+    fn display_time(frame_gaze: Option<FrameGaze>) -> Option<i64> {
+        frame_gaze.and_then(|f| f.display_time).map(Time::as_nanos)
+    }
+
+    #[test]
+    fn nearest_latched_frame_is_used() {
+        let now = Instant::now();
+        let mut frames = LatchedFrames::default();
+        for (i, nanos) in [1_000, 12_000, 23_000].into_iter().enumerate() {
+            frames.push(frame(nanos, now + Duration::from_millis(i as u64)));
+        }
+        let at = |nanos| display_time(frames.nearest(Some(Time::from_nanos(nanos)), now));
+
+        assert_eq!(at(12_000), Some(12_000));
+        assert_eq!(at(15_000), Some(12_000));
+        assert_eq!(at(20_000), Some(23_000));
+        assert_eq!(at(0), Some(1_000));
+        assert_eq!(display_time(frames.nearest(None, now)), Some(23_000));
+    }
+
+    #[test]
+    fn oldest_latched_frame_is_replaced() {
+        let now = Instant::now();
+        let mut frames = LatchedFrames::default();
+        for nanos in 1..=LATCHED_FRAME_COUNT as i64 + 1 {
+            frames.push(frame(nanos * 1_000, now));
+        }
+        let at = |nanos| display_time(frames.nearest(Some(Time::from_nanos(nanos)), now));
+        assert_eq!(at(0), Some(2_000));
+        assert_eq!(at(100_000), Some((LATCHED_FRAME_COUNT as i64 + 1) * 1_000));
+    }
+
+    #[test]
+    fn stale_latched_frames_are_ignored() {
+        let now = Instant::now();
+        let mut frames = LatchedFrames::default();
+        frames.push(frame(1_000, now));
+        frames.push(frame(2_000, now + Duration::from_millis(80)));
+
+        let later = now + FRAME_GAZE_TIMEOUT + Duration::from_millis(10);
+        assert_eq!(
+            display_time(frames.nearest(Some(Time::from_nanos(1_000)), later)),
+            Some(2_000)
+        );
+        assert!(frames.nearest(None, later + FRAME_GAZE_TIMEOUT).is_none());
+    }
+
+    #[test]
+    fn runtime_extensions_pass_through() {
+        assert!(!is_layer_only_extension("XR_KHR_vulkan_enable2", &[]));
+    }
+
+    #[test]
+    fn native_extensions_are_forwarded() {
+        let runtime = ["XR_FB_face_tracking2", "XR_EXT_user_presence"].map(String::from);
+        assert!(!is_layer_only_extension("XR_FB_face_tracking2", &runtime));
+        assert!(!is_layer_only_extension("XR_EXT_user_presence", &runtime));
+        assert!(is_layer_only_extension(
+            "XR_META_foveation_eye_tracked",
+            &runtime
+        ));
+    }
+
+    #[test]
+    fn layer_extensions_are_hidden_even_if_native() {
+        let runtime = ["XR_EXT_eye_gaze_interaction", "XR_HTC_facial_tracking"].map(String::from);
+        assert!(is_layer_only_extension(
+            "XR_EXT_eye_gaze_interaction",
+            &runtime
+        ));
+        assert!(is_layer_only_extension("XR_HTC_facial_tracking", &runtime));
+        assert!(is_layer_only_extension(raw_eye_data::EXTENSION_NAME, &[]));
+    }
+
+    #[test]
+    fn extension_names_are_read_up_to_the_nul() {
+        let mut extension = ExtensionProperties {
+            ty: StructureType::EXTENSION_PROPERTIES,
+            next: std::ptr::null_mut(),
+            extension_name: [0; openxr_sys::MAX_EXTENSION_NAME_SIZE],
+            extension_version: 1,
+        };
+        for (dst, src) in extension
+            .extension_name
+            .iter_mut()
+            .zip(b"XR_EXT_user_presence")
+        {
+            *dst = *src as c_char;
+        }
+        assert_eq!(extension_name(&extension), "XR_EXT_user_presence");
+    }
+}
@@ -6,10 +6,33 @@ use openxr_sys::{
 
 use std::os::raw::c_char;
 
+mod calibration;
+mod classify;
+mod config;
 mod dispatch;
+mod face;
+mod filter;
+mod foveation;
+mod latch;
 mod layer;
-mod server;
+mod monocular;
+mod mounting;
+mod osc_output;
+mod pose;
+mod predict;
+mod presence;
+mod pupil_offset;
+pub mod raw_eye_data;
+mod recenter;
+mod source;
+mod staleness;
+mod sys;
+mod time;
+mod vergence;
 
+/// # Safety
+///
+/// Called by the OpenXR loader with valid negotiation structures.
 #[no_mangle]
 pub unsafe extern "C" fn xrNegotiateLoaderApiLayerInterface(
     loader_info_ptr: *mut XrNegotiateLoaderInfo,
@@ -0,0 +1,234 @@
+use std::time::Duration;
+
+use quaternion_core::{self as quat, Quaternion};
+
+use crate::{config::Config, source::EyeGazeData};
+
+/// Decides which eyes are tracked and falls back to the good eye when only one is.
+pub struct EyeValidity {
+    /// An eye is lost if it wasn't updated this long before the latest sample.
+    timeout: Duration,
+    /// Minimum tracker confidence, when the tracker sends it.
+    min_confidence: f32,
+    /// Openness below which the gaze of an eye isn't trusted.
+    closed_openness: f32,
+    /// Eye used for the combined gaze when both are tracked, 0 left or 1 right, `None` to
+    /// average them.
+    dominant_eye: Option<usize>,
+}
+
+impl EyeValidity {
+    pub fn new(config: &Config) -> EyeValidity {
+        let dominant_eye = match config.get_str("eyes.dominant") {
+            Some("none") | None => None,
+            Some("left") => Some(0),
+            Some("right") => Some(1),
+            Some(eye) => {
+                println!("Unknown dominant eye {eye}, using both");
+                None
+            }
+        };
+
+        EyeValidity {
+            timeout: Duration::from_secs_f32(config.get("eyes.timeout", 0.1)),
+            min_confidence: config.get("eyes.min_confidence", 0.5),
+            closed_openness: config.get("eyes.closed_openness", 0.2),
+            dominant_eye,
+        }
+    }
+
+    /// Whether the gaze of the left and the right eye of `sample` can be trusted.
+    pub fn validity(&self, sample: &EyeGazeData) -> [bool; 2] {
+        let is_valid = |time, confidence: Option<f32>, openness: f32| {
+            sample
+                .time
+                .duration_since(time)
+                .is_ok_and(|d| d <= self.timeout)
+                && confidence.is_none_or(|c| c >= self.min_confidence)
+                && openness >= self.closed_openness
+        };
+
+        [
+            is_valid(sample.l_time, sample.l_confidence, sample.l_openness),
+            is_valid(sample.r_time, sample.r_confidence, sample.r_openness),
+        ]
+    }
+
+    /// Whether the left and the right eye of `sample` have a pupil diameter that isn't older than
+    /// the gaze timeout. A diameter that arrived after the gaze counts as fresh.
+    pub fn pupil_validity(&self, sample: &EyeGazeData) -> [bool; 2] {
+        let is_valid = |diameter: Option<f32>, time| {
+            diameter.is_some()
+                && sample
+                    .time
+                    .duration_since(time)
+                    .map_or(true, |d| d <= self.timeout)
+        };
+
+        [
+            is_valid(sample.l_pupil_diameter, sample.l_pupil_time),
+            is_valid(sample.r_pupil_diameter, sample.r_pupil_time),
+        ]
+    }
+
+    /// Give a lost eye the gaze of the other one, if that one is tracked. Also returns which
+    /// eyes are tracked.
+    ///
+    /// The gaze must already be rotated into the headset, each tracker has its own mounting.
+    pub fn with_fallback(&self, mut sample: EyeGazeData) -> (EyeGazeData, [bool; 2]) {
+        let valid = self.validity(&sample);
+        match valid {
+            [true, false] => {
+                sample.r_direction = sample.l_direction;
+                sample.r_torsion = sample.l_torsion;
+            }
+            [false, true] => {
+                sample.l_direction = sample.r_direction;
+                sample.l_torsion = sample.r_torsion;
+            }
+            _ => {}
+        }
+        (sample, valid)
+    }
+
+    /// Combine the gaze rotations of both eyes into one, preferring tracked eyes and then the
+    /// dominant eye.
+    pub fn combine(&self, eyes: [Quaternion<f32>; 2], valid: [bool; 2]) -> Quaternion<f32> {
+        match (valid, self.dominant_eye) {
+            ([true, false], _) => eyes[0],
+            ([false, true], _) => eyes[1],
+            (_, Some(eye)) => eyes[eye],
+            (_, None) => quat::slerp(eyes[0], eyes[1], 0.5),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::*;
+
+    fn at_millis(millis: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_millis(millis)
+    }
+
+    fn validity() -> EyeValidity {
+        EyeValidity::new(&Config::parse(""))
+    }
+
+    /// Both eyes updated at 1 s, looking in different directions.
+    fn tracked() -> EyeGazeData {
+        EyeGazeData {
+            l_direction: [-0.1, 0.0, -0.99],
+            r_direction: [0.1, 0.0, -0.99],
+            l_torsion: Some(0.1),
+            r_torsion: Some(-0.1),
+            l_time: at_millis(1_000),
+            r_time: at_millis(1_000),
+            time: at_millis(1_000),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn both_eyes_tracked() {
+        let sample = tracked();
+        assert_eq!(validity().with_fallback(sample), (sample, [true, true]));
+    }
+
+    #[test]
+    fn eye_times_out() {
+        let sample = EyeGazeData {
+            r_time: at_millis(1_000),
+            l_time: at_millis(850),
+            ..tracked()
+        };
+        let (fallback, valid) = validity().with_fallback(sample);
+        assert_eq!(valid, [false, true]);
+        assert_eq!(fallback.l_direction, sample.r_direction);
+        assert_eq!(fallback.l_torsion, sample.r_torsion);
+        assert_eq!(fallback.r_direction, sample.r_direction);
+
+        // Just within the timeout.
+        let sample = EyeGazeData {
+            l_time: at_millis(900),
+            ..tracked()
+        };
+        assert_eq!(validity().validity(&sample), [true, true]);
+    }
+
+    #[test]
+    fn low_confidence_eye_is_lost() {
+        let sample = EyeGazeData {
+            l_confidence: Some(0.9),
+            r_confidence: Some(0.4),
+            ..tracked()
+        };
+        let (fallback, valid) = validity().with_fallback(sample);
+        assert_eq!(valid, [true, false]);
+        assert_eq!(fallback.r_direction, sample.l_direction);
+        assert_eq!(fallback.r_torsion, sample.l_torsion);
+    }
+
+    #[test]
+    fn closed_eye_is_lost() {
+        let sample = EyeGazeData {
+            r_openness: 0.1,
+            ..tracked()
+        };
+        let (fallback, valid) = validity().with_fallback(sample);
+        assert_eq!(valid, [true, false]);
+        assert_eq!(fallback.r_direction, sample.l_direction);
+    }
+
+    #[test]
+    fn both_eyes_lost_keep_their_gaze() {
+        let sample = EyeGazeData {
+            l_openness: 0.0,
+            r_openness: 0.0,
+            ..tracked()
+        };
+        assert_eq!(validity().with_fallback(sample), (sample, [false, false]));
+    }
+
+    #[test]
+    fn combined_gaze_prefers_tracked_then_dominant_eye() {
+        let left = quat::from_axis_angle([0.0, 1.0, 0.0], 0.2);
+        let right = quat::from_axis_angle([0.0, 1.0, 0.0], -0.2);
+        let right_dominant = EyeValidity::new(&Config::parse("[eyes]\ndominant = right"));
+
+        assert_eq!(validity().combine([left, right], [true, false]), left);
+        assert_eq!(right_dominant.combine([left, right], [true, false]), left);
+        assert_eq!(right_dominant.combine([left, right], [true, true]), right);
+        assert_eq!(right_dominant.combine([left, right], [false, false]), right);
+
+        let average = validity().combine([left, right], [true, true]);
+        assert!((average.0 - 1.0).abs() < 1e-6 && quat::norm(average.1) < 1e-6);
+    }
+
+    #[test]
+    fn pupil_is_invalid_once_stale() {
+        let sample = EyeGazeData {
+            l_pupil_diameter: Some(3.5),
+            r_pupil_diameter: Some(3.5),
+            l_pupil_time: at_millis(1_000),
+            r_pupil_time: at_millis(1_450),
+            time: at_millis(1_500),
+            ..Default::default()
+        };
+        assert_eq!(validity().pupil_validity(&sample), [false, true]);
+    }
+
+    #[test]
+    fn pupil_newer_than_the_gaze_is_valid() {
+        let sample = EyeGazeData {
+            l_pupil_diameter: Some(3.5),
+            l_pupil_time: at_millis(1_600),
+            r_pupil_time: at_millis(1_600),
+            time: at_millis(1_500),
+            ..Default::default()
+        };
+        assert_eq!(validity().pupil_validity(&sample), [true, false]);
+    }
+}
@@ -0,0 +1,101 @@
+use quaternion_core::{self as quat, Quaternion, RotationSequence, RotationType, Vector3};
+
+use crate::{config::Config, pose, source::EyeGazeData};
+
+/// How each eye tracker camera is rotated relative to the headset, applied to the gaze as it
+/// comes from the tracker, before calibration.
+///
+/// Configured in degrees with `mounting.<eye>_roll`, `_pitch` and `_yaw`. Pitch is positive
+/// up, yaw positive left and roll positive counter-clockwise from the user's point of view, the
+/// rotations are applied yaw first.
+pub struct Mounting {
+    rotations: [Quaternion<f32>; 2],
+}
+
+impl Mounting {
+    pub fn new(config: &Config) -> Mounting {
+        let rotation = |eye: &str| {
+            let angle = |name: &str| {
+                config
+                    .get::<f32>(&format!("mounting.{eye}_{name}"), 0.0)
+                    .to_radians()
+            };
+            quat::from_euler_angles(
+                RotationType::Intrinsic,
+                RotationSequence::YXZ,
+                [angle("yaw"), angle("pitch"), angle("roll")],
+            )
+        };
+
+        Mounting {
+            rotations: [rotation("left"), rotation("right")],
+        }
+    }
+
+    fn rotation(&self, is_left: bool) -> Quaternion<f32> {
+        self.rotations[if is_left { 0 } else { 1 }]
+    }
+
+    /// The gaze of both eyes of `sample` rotated from the trackers into the headset. The roll of
+    /// a tracker adds to the torsion, if the tracker sends it.
+    pub fn apply(&self, mut sample: EyeGazeData) -> EyeGazeData {
+        let rotate = |direction: &mut Vector3<f32>, torsion: &mut Option<f32>, is_left: bool| {
+            let q = quat::mul(
+                self.rotation(is_left),
+                pose::look_rotation(*direction, torsion.unwrap_or(0.0)),
+            );
+            *direction = quat::point_rotation(q, pose::FORWARD);
+            *torsion = torsion.map(|_| pose::torsion(q));
+        };
+        rotate(&mut sample.l_direction, &mut sample.l_torsion, true);
+        rotate(&mut sample.r_direction, &mut sample.r_torsion, false);
+        sample
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mounting(config: &str) -> Mounting {
+        Mounting::new(&Config::parse(config))
+    }
+
+    fn assert_near(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{a} != {b}");
+    }
+
+    #[test]
+    fn no_mounting_keeps_the_gaze() {
+        let sample = EyeGazeData {
+            l_direction: pose::direction_from_angles(0.2, -0.1),
+            r_torsion: Some(0.1),
+            ..Default::default()
+        };
+        let mounted = mounting("").apply(sample);
+        for (a, b) in mounted.l_direction.iter().zip(sample.l_direction) {
+            assert_near(*a, b);
+        }
+        assert_near(mounted.r_torsion.unwrap(), 0.1);
+        assert_eq!(mounted.l_torsion, None);
+    }
+
+    #[test]
+    fn pitched_tracker_looks_up() {
+        let mounted = mounting("[mounting]\nleft_pitch = 10").apply(EyeGazeData::default());
+        let (pitch, yaw) = pose::angles_from_direction(mounted.l_direction);
+        assert_near(pitch, 10f32.to_radians());
+        assert_near(yaw, 0.0);
+        assert_eq!(mounted.r_direction, pose::FORWARD);
+    }
+
+    #[test]
+    fn roll_adds_to_the_torsion() {
+        let sample = EyeGazeData {
+            r_torsion: Some(0.1),
+            ..Default::default()
+        };
+        let mounted = mounting("[mounting]\nright_roll = 5").apply(sample);
+        assert_near(mounted.r_torsion.unwrap(), 0.1 + 5f32.to_radians());
+    }
+}
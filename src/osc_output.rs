@@ -0,0 +1,54 @@
+use std::net::UdpSocket;
+
+use rosc::{OscMessage, OscPacket, OscType};
+
+use crate::{classify::GazeEvent, config::Config};
+
+/// Sends what the layer works out from the gaze back out over OSC.
+pub struct OscOutput {
+    socket: UdpSocket,
+    gaze_event_address: String,
+}
+
+impl OscOutput {
+    /// `None` unless `osc_output.target` is set to a `host:port`.
+    pub fn new(config: &Config) -> Option<OscOutput> {
+        let target = config.get_str("osc_output.target")?;
+
+        let socket = UdpSocket::bind(("0.0.0.0", 0))
+            .and_then(|socket| socket.connect(target).map(|_| socket));
+        match socket {
+            Ok(socket) => Some(OscOutput {
+                socket,
+                gaze_event_address: config
+                    .get_string("osc_output.gaze_event_address", "/tracking/eye/GazeEvent"),
+            }),
+            Err(e) => {
+                println!("Can't send OSC to {target}: {e}");
+                None
+            }
+        }
+    }
+
+    /// Send the event as an int, 0 fixation, 1 saccade or 2 blink, followed by the angular
+    /// velocity in radians per second.
+    pub fn send_gaze_event(&self, event: GazeEvent, angular_velocity: f32) {
+        self.send(OscMessage {
+            addr: self.gaze_event_address.clone(),
+            args: vec![OscType::Int(event as i32), OscType::Float(angular_velocity)],
+        });
+    }
+
+    fn send(&self, msg: OscMessage) {
+        let buf = match rosc::encoder::encode(&OscPacket::Message(msg)) {
+            Ok(buf) => buf,
+            Err(e) => {
+                println!("Can't encode OSC message: {:?}", e);
+                return;
+            }
+        };
+
+        // Nobody listening isn't an error worth reporting at the frame rate.
+        let _ = self.socket.send(&buf);
+    }
+}
@@ -0,0 +1,245 @@
+//! Gaze and pose math in OpenXR conventions.
+//!
+//! OpenXR spaces are right-handed with +X right, +Y up and -Z forward. Gaze is carried as a unit
+//! direction vector in that frame. Where angles are needed, pitch is positive up and yaw is
+//! positive left, both in radians.
+
+use openxr_sys::{Posef, Quaternionf, Vector3f};
+use quaternion_core::{self as quat, Quaternion, Vector3};
+
+pub const FORWARD: Vector3<f32> = [0.0, 0.0, -1.0];
+pub const UP: Vector3<f32> = [0.0, 1.0, 0.0];
+
+/// Unit direction for a gaze given as pitch and yaw.
+pub fn direction_from_angles(pitch: f32, yaw: f32) -> Vector3<f32> {
+    let (sin_pitch, cos_pitch) = pitch.sin_cos();
+    let (sin_yaw, cos_yaw) = yaw.sin_cos();
+    [-sin_yaw * cos_pitch, sin_pitch, -cos_yaw * cos_pitch]
+}
+
+/// Pitch and yaw of a direction, the inverse of `direction_from_angles`.
+pub fn angles_from_direction(direction: Vector3<f32>) -> (f32, f32) {
+    let [x, y, z] = direction;
+    (y.atan2(x.hypot(z)), (-x).atan2(-z))
+}
+
+/// Normalize `direction`, or look forward if it has no length.
+pub fn normalize_direction(direction: Vector3<f32>) -> Vector3<f32> {
+    let norm = quat::norm(direction);
+    if norm > f32::EPSILON && norm.is_finite() {
+        quat::scale(norm.recip(), direction)
+    } else {
+        FORWARD
+    }
+}
+
+/// Orientation of an eye looking in `direction`, rotated by `torsion` radians about the line of
+/// sight, counter-clockwise from the user's point of view.
+///
+/// Without torsion the eye follows Listing's law: it turns from looking forward about an axis
+/// perpendicular to the forward direction, so there's no roll that depends on the order of
+/// Euler angles.
+pub fn look_rotation(direction: Vector3<f32>, torsion: f32) -> Quaternion<f32> {
+    let direction = normalize_direction(direction);
+
+    // Half-way quaternion between the two unit vectors, well defined unless looking backwards.
+    let w = 1.0 + quat::dot(FORWARD, direction);
+    let listing = if w > 1e-6 {
+        quat::normalize((w, quat::cross(FORWARD, direction)))
+    } else {
+        (0.0, UP)
+    };
+
+    if torsion == 0.0 {
+        return listing;
+    }
+    let roll = quat::from_axis_angle(quat::scale(-1.0, direction), torsion);
+    quat::mul(roll, listing)
+}
+
+/// Torsion of the eye rotation `q`, the twist about its gaze direction left after turning
+/// forward to that direction by Listing's law, as in `look_rotation`.
+pub fn torsion(q: Quaternion<f32>) -> f32 {
+    let direction = quat::point_rotation(q, FORWARD);
+    let twist = quat::mul(q, quat::conj(look_rotation(direction, 0.0)));
+    let angle = 2.0 * quat::dot(twist.1, quat::scale(-1.0, direction)).atan2(twist.0);
+    // The same twist the other way round, as the double cover of `q` allows.
+    if angle > std::f32::consts::PI {
+        angle - std::f32::consts::TAU
+    } else if angle < -std::f32::consts::PI {
+        angle + std::f32::consts::TAU
+    } else {
+        angle
+    }
+}
+
+/// The pose at `position` with `orientation` relative to `parent`, in the space of `parent`.
+pub fn compose(parent: &Posef, position: Vector3<f32>, orientation: Quaternion<f32>) -> Posef {
+    let parent_q = from_quaternionf(parent.orientation);
+    Posef {
+        orientation: to_quaternionf(quat::mul(parent_q, orientation)),
+        position: to_vector3f(quat::add(
+            from_vector3f(parent.position),
+            quat::point_rotation(parent_q, position),
+        )),
+    }
+}
+
+pub fn to_quaternionf(q: Quaternion<f32>) -> Quaternionf {
+    Quaternionf {
+        w: q.0,
+        x: q.1[0],
+        y: q.1[1],
+        z: q.1[2],
+    }
+}
+
+pub fn from_quaternionf(q: Quaternionf) -> Quaternion<f32> {
+    (q.w, [q.x, q.y, q.z])
+}
+
+pub fn to_vector3f(v: Vector3<f32>) -> Vector3f {
+    Vector3f {
+        x: v[0],
+        y: v[1],
+        z: v[2],
+    }
+}
+
+pub fn from_vector3f(v: Vector3f) -> Vector3<f32> {
+    [v.x, v.y, v.z]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-5;
+    const RIGHT: Vector3<f32> = [1.0, 0.0, 0.0];
+
+    fn assert_vec_eq(a: Vector3<f32>, b: Vector3<f32>) {
+        let distance = quat::norm(quat::sub(a, b));
+        assert!(distance < EPSILON, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn zero_angles_look_forward() {
+        assert_vec_eq(direction_from_angles(0.0, 0.0), FORWARD);
+    }
+
+    #[test]
+    fn positive_pitch_looks_up() {
+        let direction = direction_from_angles(30f32.to_radians(), 0.0);
+        assert!(direction[1] > 0.0);
+        assert_vec_eq(direction_from_angles(90f32.to_radians(), 0.0), UP);
+    }
+
+    #[test]
+    fn positive_yaw_looks_left() {
+        let direction = direction_from_angles(0.0, 30f32.to_radians());
+        assert!(direction[0] < 0.0);
+        assert_vec_eq(
+            direction_from_angles(0.0, 90f32.to_radians()),
+            quat::scale(-1.0, RIGHT),
+        );
+    }
+
+    #[test]
+    fn angles_round_trip() {
+        for pitch in [-60.0f32, -10.0, 0.0, 25.0, 89.0] {
+            for yaw in [-80.0f32, -5.0, 0.0, 40.0, 120.0] {
+                let (p, y) = angles_from_direction(direction_from_angles(
+                    pitch.to_radians(),
+                    yaw.to_radians(),
+                ));
+                assert!((p - pitch.to_radians()).abs() < EPSILON, "{pitch} {yaw}");
+                assert!((y - yaw.to_radians()).abs() < EPSILON, "{pitch} {yaw}");
+            }
+        }
+    }
+
+    #[test]
+    fn look_rotation_forward_is_identity() {
+        let q = look_rotation(FORWARD, 0.0);
+        assert!((q.0 - 1.0).abs() < EPSILON);
+        assert_vec_eq(q.1, [0.0; 3]);
+    }
+
+    #[test]
+    fn look_rotation_turns_forward_to_direction() {
+        let direction = direction_from_angles(20f32.to_radians(), -35f32.to_radians());
+        let q = look_rotation(direction, 0.0);
+        assert_vec_eq(quat::point_rotation(q, FORWARD), direction);
+    }
+
+    #[test]
+    fn look_rotation_follows_listings_law() {
+        // The rotation axis is perpendicular to the forward direction, so an oblique gaze has
+        // no twist about the forward axis.
+        let q = look_rotation(direction_from_angles(0.4, 0.6), 0.0);
+        assert!(q.1[2].abs() < EPSILON);
+    }
+
+    #[test]
+    fn looking_up_keeps_right_vector() {
+        let q = look_rotation(UP, 0.0);
+        assert_vec_eq(quat::point_rotation(q, RIGHT), RIGHT);
+        assert_vec_eq(quat::point_rotation(q, UP), quat::scale(-1.0, FORWARD));
+    }
+
+    #[test]
+    fn positive_torsion_is_counter_clockwise() {
+        let q = look_rotation(FORWARD, 90f32.to_radians());
+        assert_vec_eq(quat::point_rotation(q, RIGHT), UP);
+        assert_vec_eq(quat::point_rotation(q, FORWARD), FORWARD);
+    }
+
+    #[test]
+    fn composed_pose_keeps_the_gaze_ray() {
+        // Head turned 90° left and raised, gazing from a point in front of it, up and right.
+        let head = Posef {
+            orientation: to_quaternionf(quat::from_axis_angle(UP, 90f32.to_radians())),
+            position: to_vector3f([1.0, 1.6, -2.0]),
+        };
+        let origin = [0.0, 0.0, -0.5];
+        let gaze_q = look_rotation(direction_from_angles(0.2, -0.3), 0.0);
+        let pose = compose(&head, origin, gaze_q);
+
+        // Any point along the gaze relative to the head lands on the gaze of the composed pose.
+        let head_q = from_quaternionf(head.orientation);
+        let along = quat::add(origin, quat::point_rotation(gaze_q, [0.0, 0.0, -2.0]));
+        let expected = quat::add(
+            from_vector3f(head.position),
+            quat::point_rotation(head_q, along),
+        );
+        let actual = quat::add(
+            from_vector3f(pose.position),
+            quat::point_rotation(from_quaternionf(pose.orientation), [0.0, 0.0, -2.0]),
+        );
+        assert_vec_eq(actual, expected);
+        assert_vec_eq(from_vector3f(pose.position), [1.0 - 0.5, 1.6, -2.0]);
+    }
+
+    #[test]
+    fn torsion_round_trips() {
+        for torsion in [-1.0f32, -0.25, 0.0, 0.3, 2.5] {
+            let q = look_rotation(direction_from_angles(0.35, -0.5), torsion);
+            assert!((super::torsion(q) - torsion).abs() < 1e-4, "{torsion}");
+        }
+    }
+
+    #[test]
+    fn torsion_keeps_direction() {
+        let direction = direction_from_angles(-0.3, 0.2);
+        let q = look_rotation(direction, 0.25);
+        assert_vec_eq(quat::point_rotation(q, FORWARD), direction);
+    }
+
+    #[test]
+    fn openxr_quaternion_layout() {
+        let q = look_rotation(direction_from_angles(0.1, 0.2), 0.3);
+        let xr = to_quaternionf(q);
+        assert_eq!((xr.w, xr.x, xr.y, xr.z), (q.0, q.1[0], q.1[1], q.1[2]));
+        assert_eq!(from_quaternionf(xr), q);
+    }
+}
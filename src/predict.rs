@@ -0,0 +1,372 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant, SystemTime},
+};
+
+use quaternion_core::{self as quat, Quaternion, Vector3};
+
+use crate::{classify::GazeEvent, config::Config, source::EyeGazeData};
+
+/// Predictions kept until the sample they predict arrives.
+const MAX_PENDING_PREDICTIONS: usize = 64;
+
+/// How the gaze is extrapolated to the requested time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PredictorKind {
+    None,
+    /// Keep turning each eye at its latest angular velocity.
+    ConstantVelocity,
+}
+
+/// Extrapolates the gaze from the sample history to the time the app asks for, usually the
+/// predicted display time, to hide the latency of the tracker and the network.
+///
+/// Configured in the `predict` section. Saccades are ballistic and stop abruptly, so their
+/// extrapolation is clamped harder than the slow movements during fixations.
+pub struct Predictor {
+    kind: PredictorKind,
+    /// Never predict further ahead of the latest sample than this.
+    max_horizon: Duration,
+    /// Samples this far back from the latest are used for the velocity.
+    velocity_window: Duration,
+    /// Radians per second.
+    max_velocity: f32,
+    /// Radians, the most the prediction turns the gaze.
+    max_angle: f32,
+    /// Radians, the most the prediction turns the gaze during a saccade.
+    saccade_max_angle: f32,
+    metrics: Option<PredictionMetrics>,
+}
+
+impl Predictor {
+    pub fn new(config: &Config) -> Predictor {
+        let kind = match config.get_str("predict.type") {
+            Some("none") | None => PredictorKind::None,
+            Some("constant_velocity") => PredictorKind::ConstantVelocity,
+            Some(kind) => {
+                println!("Unknown gaze predictor {kind}, not predicting");
+                PredictorKind::None
+            }
+        };
+        let degrees = |key: &str, default: f32| config.get::<f32>(key, default).to_radians();
+        let metrics_interval = config.get::<f32>("predict.metrics_interval", 10.0);
+
+        Predictor {
+            kind,
+            max_horizon: Duration::from_secs_f32(config.get("predict.max_horizon", 0.05)),
+            velocity_window: Duration::from_secs_f32(config.get("predict.velocity_window", 0.02)),
+            max_velocity: degrees("predict.max_velocity", 700.0),
+            max_angle: degrees("predict.max_angle", 10.0),
+            saccade_max_angle: degrees("predict.saccade_max_angle", 2.0),
+            metrics: (kind != PredictorKind::None && metrics_interval > 0.0)
+                .then(|| PredictionMetrics::new(Duration::from_secs_f32(metrics_interval))),
+        }
+    }
+
+    /// How the gaze turns from the last sample of `history` until `target`. `history` is the
+    /// calibrated gaze, oldest first, and `event` what the eyes are doing at its last sample.
+    pub fn predict(
+        &mut self,
+        history: &[EyeGazeData],
+        event: GazeEvent,
+        target: SystemTime,
+    ) -> Prediction {
+        let Some(last) = history.last() else {
+            return Prediction::NONE;
+        };
+        if self.kind == PredictorKind::None {
+            return Prediction::NONE;
+        }
+
+        if let Some(metrics) = &mut self.metrics {
+            metrics.observe(history);
+        }
+
+        let horizon = target
+            .duration_since(last.time)
+            .unwrap_or_default()
+            .min(self.max_horizon)
+            .as_secs_f32();
+        let max_angle = match event {
+            GazeEvent::Fixation => self.max_angle,
+            GazeEvent::Saccade => self.saccade_max_angle,
+            // The gaze of closed eyes is meaningless.
+            GazeEvent::Blink => 0.0,
+        };
+
+        let mut prediction = Prediction::NONE;
+        if horizon > 0.0 && max_angle > 0.0 {
+            let velocities = self.angular_velocities(history);
+            for (rotation, velocity) in prediction.rotations.iter_mut().zip(velocities) {
+                if let Some((axis, speed)) = velocity {
+                    let angle = (speed.min(self.max_velocity) * horizon).min(max_angle);
+                    *rotation = quat::from_axis_angle(axis, angle);
+                }
+            }
+        }
+
+        if let Some(metrics) = &mut self.metrics {
+            metrics.push(target, &prediction.apply(*last), last);
+        }
+
+        prediction
+    }
+
+    /// Rotation axis and speed in radians per second of each eye over the last
+    /// `velocity_window` of `history`.
+    fn angular_velocities(&self, history: &[EyeGazeData]) -> [Option<(Vector3<f32>, f32)>; 2] {
+        let Some(last) = history.last() else {
+            return [None; 2];
+        };
+        let Some(first) = history.iter().find(|s| {
+            last.time
+                .duration_since(s.time)
+                .is_ok_and(|d| d <= self.velocity_window)
+        }) else {
+            return [None; 2];
+        };
+
+        let dt = last.time.duration_since(first.time).unwrap_or_default();
+        if dt.is_zero() {
+            return [None; 2];
+        }
+
+        let velocity = |from: Vector3<f32>, to: Vector3<f32>| {
+            let axis = quat::cross(from, to);
+            let sin = quat::norm(axis);
+            if sin < f32::EPSILON {
+                return None;
+            }
+            let angle = sin.atan2(quat::dot(from, to));
+            Some((quat::scale(sin.recip(), axis), angle / dt.as_secs_f32()))
+        };
+
+        [
+            velocity(first.l_direction, last.l_direction),
+            velocity(first.r_direction, last.r_direction),
+        ]
+    }
+}
+
+/// Rotation of each eye from the latest sample to the predicted time.
+#[derive(Debug, Clone, Copy)]
+pub struct Prediction {
+    rotations: [Quaternion<f32>; 2],
+}
+
+impl Prediction {
+    const NONE: Prediction = Prediction {
+        rotations: [(1.0, [0.0; 3]); 2],
+    };
+
+    pub fn apply(&self, mut sample: EyeGazeData) -> EyeGazeData {
+        sample.l_direction = quat::point_rotation(self.rotations[0], sample.l_direction);
+        sample.r_direction = quat::point_rotation(self.rotations[1], sample.r_direction);
+        sample
+    }
+}
+
+/// Target time, predicted and unpredicted directions of each eye.
+type PendingPrediction = (SystemTime, [Vector3<f32>; 2], [Vector3<f32>; 2]);
+
+/// Compares predictions with the samples that arrive for their time, and prints how much better
+/// they are than using the latest sample.
+struct PredictionMetrics {
+    interval: Duration,
+    last_report: Instant,
+    pending: VecDeque<PendingPrediction>,
+    count: u32,
+    /// Sums of the squared errors in radians².
+    predicted_error: f32,
+    unpredicted_error: f32,
+    /// Radians.
+    max_error: f32,
+}
+
+impl PredictionMetrics {
+    fn new(interval: Duration) -> PredictionMetrics {
+        PredictionMetrics {
+            interval,
+            last_report: Instant::now(),
+            pending: VecDeque::new(),
+            count: 0,
+            predicted_error: 0.0,
+            unpredicted_error: 0.0,
+            max_error: 0.0,
+        }
+    }
+
+    fn push(&mut self, target: SystemTime, predicted: &EyeGazeData, unpredicted: &EyeGazeData) {
+        if self.pending.len() == MAX_PENDING_PREDICTIONS {
+            self.pending.pop_front();
+        }
+        self.pending.push_back((
+            target,
+            [predicted.l_direction, predicted.r_direction],
+            [unpredicted.l_direction, unpredicted.r_direction],
+        ));
+    }
+
+    /// Score the pending predictions whose time has been reached by `history`.
+    fn observe(&mut self, history: &[EyeGazeData]) {
+        let angle = |u: Vector3<f32>, v| quat::norm(quat::cross(u, v)).atan2(quat::dot(u, v));
+
+        while let Some(&(target, predicted, unpredicted)) = self.pending.front() {
+            if history.last().is_none_or(|s| s.time < target) {
+                break;
+            }
+            self.pending.pop_front();
+
+            let Some(actual) = history.iter().find(|s| s.time >= target) else {
+                continue;
+            };
+            let actual = [actual.l_direction, actual.r_direction];
+            for eye in 0..2 {
+                let error = angle(predicted[eye], actual[eye]);
+                self.predicted_error += error * error;
+                self.unpredicted_error += angle(unpredicted[eye], actual[eye]).powi(2);
+                self.max_error = self.max_error.max(error);
+                self.count += 1;
+            }
+        }
+
+        if self.last_report.elapsed() >= self.interval && self.count > 0 {
+            let rms = |sum: f32| (sum / self.count as f32).sqrt().to_degrees();
+            println!(
+                "Gaze prediction error over {} eye samples: RMS {:.2}° (without prediction {:.2}°), max {:.2}°",
+                self.count,
+                rms(self.predicted_error),
+                rms(self.unpredicted_error),
+                self.max_error.to_degrees(),
+            );
+            self.last_report = Instant::now();
+            self.count = 0;
+            self.predicted_error = 0.0;
+            self.unpredicted_error = 0.0;
+            self.max_error = 0.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pose;
+
+    const EPSILON: f32 = 1e-3;
+    /// Samples are this many milliseconds apart.
+    const INTERVAL_MS: u64 = 8;
+
+    fn predictor() -> Predictor {
+        Predictor::new(&Config::parse(
+            "[predict]\ntype = constant_velocity\nmetrics_interval = 0\n",
+        ))
+    }
+
+    fn at_ms(ms: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_millis(ms)
+    }
+
+    /// Both eyes turning left at `velocity` degrees per second, the last sample at yaw 0.
+    fn turning_history(velocity: f32) -> Vec<EyeGazeData> {
+        (0..10)
+            .map(|i| {
+                let yaw = velocity.to_radians() * ((i as f32 - 9.0) * INTERVAL_MS as f32 / 1000.0);
+                let direction = pose::direction_from_angles(0.0, yaw);
+                EyeGazeData {
+                    l_direction: direction,
+                    r_direction: direction,
+                    time: at_ms(1000 + i * INTERVAL_MS),
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+
+    /// Predicted yaw of each eye in degrees, `ms` after the last sample of `history`.
+    fn predicted_yaw(history: &[EyeGazeData], event: GazeEvent, ms: u64) -> [f32; 2] {
+        let last = history.last().unwrap();
+        let target = last.time + Duration::from_millis(ms);
+        let predicted = predictor().predict(history, event, target).apply(*last);
+        [predicted.l_direction, predicted.r_direction]
+            .map(|d| pose::angles_from_direction(d).1.to_degrees())
+    }
+
+    #[test]
+    fn extrapolates_at_constant_velocity() {
+        let history = turning_history(100.0);
+        for yaw in predicted_yaw(&history, GazeEvent::Fixation, 20) {
+            assert!((yaw - 2.0).abs() < EPSILON, "{yaw}");
+        }
+    }
+
+    #[test]
+    fn still_gaze_isnt_moved() {
+        let history = turning_history(0.0);
+        assert_eq!(predicted_yaw(&history, GazeEvent::Fixation, 20), [0.0; 2]);
+    }
+
+    #[test]
+    fn past_targets_arent_predicted() {
+        let history = turning_history(100.0);
+        let target = history.last().unwrap().time - Duration::from_millis(5);
+        let prediction = predictor().predict(&history, GazeEvent::Fixation, target);
+        assert_eq!(prediction.rotations, Prediction::NONE.rotations);
+    }
+
+    #[test]
+    fn horizon_is_capped() {
+        // The default horizon is 50 ms.
+        let history = turning_history(100.0);
+        for yaw in predicted_yaw(&history, GazeEvent::Fixation, 200) {
+            assert!((yaw - 5.0).abs() < EPSILON, "{yaw}");
+        }
+    }
+
+    #[test]
+    fn saccades_are_clamped_harder() {
+        // 8° unclamped, the limits are 10° for fixations and 2° for saccades.
+        let history = turning_history(400.0);
+        for yaw in predicted_yaw(&history, GazeEvent::Fixation, 20) {
+            assert!((yaw - 8.0).abs() < EPSILON, "{yaw}");
+        }
+        for yaw in predicted_yaw(&history, GazeEvent::Saccade, 20) {
+            assert!((yaw - 2.0).abs() < EPSILON, "{yaw}");
+        }
+    }
+
+    #[test]
+    fn blinks_arent_predicted() {
+        let history = turning_history(100.0);
+        assert_eq!(predicted_yaw(&history, GazeEvent::Blink, 20), [0.0; 2]);
+    }
+
+    #[test]
+    fn metrics_compare_with_latest_sample() {
+        let mut metrics = PredictionMetrics::new(Duration::from_secs(3600));
+        let actual = pose::direction_from_angles(0.0, 0.1);
+        let unpredicted = EyeGazeData::default();
+        let predicted = EyeGazeData {
+            l_direction: actual,
+            r_direction: pose::direction_from_angles(0.0, 0.08),
+            ..unpredicted
+        };
+        metrics.push(at_ms(1020), &predicted, &unpredicted);
+
+        let sample = |ms: u64| EyeGazeData {
+            l_direction: actual,
+            r_direction: actual,
+            time: at_ms(ms),
+            ..Default::default()
+        };
+        // Not scored until a sample reaches the target time.
+        metrics.observe(&[sample(1016)]);
+        assert_eq!((metrics.count, metrics.pending.len()), (0, 1));
+
+        metrics.observe(&[sample(1016), sample(1024)]);
+        assert_eq!((metrics.count, metrics.pending.len()), (2, 0));
+        assert!((metrics.predicted_error - 0.02f32.powi(2)).abs() < 1e-6);
+        assert!((metrics.unpredicted_error - 2.0 * 0.1f32.powi(2)).abs() < 1e-6);
+        assert!((metrics.max_error - 0.02).abs() < 1e-6);
+    }
+}
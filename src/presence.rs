@@ -0,0 +1,160 @@
+use std::time::{Duration, Instant};
+
+use crate::{config::Config, source::EyeGazeData};
+
+/// Openness below which an eye is considered closed.
+const CLOSED_OPENNESS: f32 = 0.2;
+
+/// Decides whether the user is present from the eye tracker state, for `XR_EXT_user_presence`.
+pub struct UserPresence {
+    /// The user is absent when no eye data arrives for this long.
+    no_data_timeout: Duration,
+    /// The user is absent when both eyes stay closed for this long, `None` to ignore closed eyes.
+    eyes_closed_timeout: Option<Duration>,
+
+    eyes_closed_since: Option<Instant>,
+    is_present: bool,
+}
+
+impl UserPresence {
+    pub fn new(config: &Config) -> UserPresence {
+        let eyes_closed_timeout = config.get("presence.eyes_closed_timeout", 10.0f32);
+
+        UserPresence {
+            no_data_timeout: Duration::from_secs_f32(config.get("presence.no_data_timeout", 1.0)),
+            eyes_closed_timeout: (eyes_closed_timeout > 0.0)
+                .then(|| Duration::from_secs_f32(eyes_closed_timeout)),
+            eyes_closed_since: None,
+            is_present: false,
+        }
+    }
+
+    pub fn is_present(&self) -> bool {
+        self.is_present
+    }
+
+    /// Update the state from the latest eye data, returns the new state if it changed.
+    pub fn update(&mut self, eye_gaze_data: &EyeGazeData) -> Option<bool> {
+        self.update_at(eye_gaze_data, Instant::now())
+    }
+
+    fn update_at(&mut self, eye_gaze_data: &EyeGazeData, now: Instant) -> Option<bool> {
+        let has_data = eye_gaze_data
+            .received
+            .is_some_and(|t| now - t < self.no_data_timeout);
+
+        let eyes_closed = eye_gaze_data.l_openness < CLOSED_OPENNESS
+            && eye_gaze_data.r_openness < CLOSED_OPENNESS;
+        if !eyes_closed {
+            self.eyes_closed_since = None;
+        } else if self.eyes_closed_since.is_none() {
+            self.eyes_closed_since = Some(now);
+        }
+
+        let closed_too_long = self
+            .eyes_closed_timeout
+            .zip(self.eyes_closed_since)
+            .is_some_and(|(timeout, since)| now - since >= timeout);
+
+        let is_present = has_data && !closed_too_long;
+        if is_present == self.is_present {
+            return None;
+        }
+
+        self.is_present = is_present;
+        Some(is_present)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn presence(eyes_closed_timeout: f32) -> UserPresence {
+        UserPresence::new(&Config::parse(&format!(
+            "[presence]\nno_data_timeout = 1\neyes_closed_timeout = {eyes_closed_timeout}"
+        )))
+    }
+
+    fn received(at: Instant, openness: f32) -> EyeGazeData {
+        EyeGazeData {
+            l_openness: openness,
+            r_openness: openness,
+            received: Some(at),
+            ..Default::default()
+        }
+    }
+
+    fn seconds(s: f32) -> Duration {
+        Duration::from_secs_f32(s)
+    }
+
+    #[test]
+    fn absent_without_data() {
+        let mut presence = presence(10.0);
+        assert_eq!(presence.update(&EyeGazeData::default()), None);
+        assert!(!presence.is_present());
+    }
+
+    #[test]
+    fn absent_after_no_data_timeout() {
+        let mut presence = presence(10.0);
+        let start = Instant::now();
+        let data = received(start, 1.0);
+        assert_eq!(presence.update_at(&data, start), Some(true));
+        assert_eq!(presence.update_at(&data, start + seconds(0.9)), None);
+        assert_eq!(presence.update_at(&data, start + seconds(1.0)), Some(false));
+        assert!(!presence.is_present());
+    }
+
+    #[test]
+    fn absent_after_eyes_closed_timeout() {
+        let mut presence = presence(10.0);
+        let start = Instant::now();
+        assert_eq!(presence.update_at(&received(start, 1.0), start), Some(true));
+
+        // The eyes close, and stay closed with data still arriving.
+        for s in [0.5, 5.0, 10.4] {
+            let now = start + seconds(s);
+            assert_eq!(presence.update_at(&received(now, 0.0), now), None);
+        }
+        let now = start + seconds(10.5);
+        assert_eq!(presence.update_at(&received(now, 0.0), now), Some(false));
+
+        let now = start + seconds(11.0);
+        assert_eq!(presence.update_at(&received(now, 1.0), now), Some(true));
+    }
+
+    #[test]
+    fn blinks_restart_the_eyes_closed_timeout() {
+        let mut presence = presence(1.0);
+        let start = Instant::now();
+        presence.update_at(&received(start, 0.0), start);
+        let now = start + seconds(0.9);
+        presence.update_at(&received(now, 1.0), now);
+        let now = start + seconds(1.5);
+        assert_eq!(presence.update_at(&received(now, 0.0), now), None);
+        assert!(presence.is_present());
+    }
+
+    #[test]
+    fn zero_eyes_closed_timeout_ignores_closed_eyes() {
+        let mut presence = presence(0.0);
+        let start = Instant::now();
+        assert_eq!(presence.update_at(&received(start, 0.0), start), Some(true));
+        let now = start + seconds(3600.0);
+        assert_eq!(presence.update_at(&received(now, 0.0), now), None);
+        assert!(presence.is_present());
+    }
+
+    #[test]
+    fn changes_are_reported_once() {
+        let mut presence = presence(10.0);
+        let start = Instant::now();
+        let changes: Vec<bool> = [0.0, 0.1, 0.2, 2.0, 2.1]
+            .into_iter()
+            .filter_map(|s| presence.update_at(&received(start, 1.0), start + seconds(s)))
+            .collect();
+        assert_eq!(changes, [true, false]);
+    }
+}
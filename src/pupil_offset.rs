@@ -0,0 +1,215 @@
+use openxr_sys::{Posef, ViewConfigurationType, ViewState, ViewStateFlags};
+use quaternion_core::{self as quat, Quaternion, QuaternionOps, Vector3};
+
+use crate::{config::Config, pose};
+
+/// What the pupil offset does to the views.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Leave the views alone.
+    Off,
+    /// Move the views to the pupils.
+    Position,
+    /// Also turn the views a fraction of the way towards the gaze.
+    PositionRotation,
+}
+
+/// Moves the views to where the pupils are for the current gaze.
+///
+/// The eye rotates about a centre behind the pupil. The view poses reported by the runtime are
+/// taken as the pupil positions while looking straight ahead, so the centre is `radius` behind
+/// them along the view axis, and the pupil is `radius` in front of the centre along the gaze.
+///
+/// Configured with `pupil_offset.mode`, `off`, `position` or `rotation`, and the radii in
+/// millimetres with `pupil_offset.left_radius_mm` and `right_radius_mm`.
+pub struct PupilOffset {
+    mode: Mode,
+    /// Distance from the rotation centre to the pupil of each eye, in metres.
+    radii: [f32; 2],
+    /// How far the views turn towards the gaze in `rotation` mode, 0 to 1.
+    rotation_fraction: f32,
+}
+
+impl PupilOffset {
+    pub fn new(config: &Config) -> PupilOffset {
+        let mode = match config.get_str("pupil_offset.mode") {
+            Some("position") | None => Mode::Position,
+            Some("off") => Mode::Off,
+            Some("rotation") => Mode::PositionRotation,
+            Some(mode) => {
+                println!("Unknown pupil offset mode {mode}, offsetting the position");
+                Mode::Position
+            }
+        };
+        let radius = |eye: &str| {
+            config.get::<f32>(&format!("pupil_offset.{eye}_radius_mm"), DEFAULT_RADIUS_MM) / 1000.0
+        };
+
+        PupilOffset {
+            mode,
+            radii: [radius("left"), radius("right")],
+            rotation_fraction: config
+                .get::<f32>("pupil_offset.rotation_fraction", 0.1)
+                .clamp(0.0, 1.0),
+        }
+    }
+
+    /// Whether views returned with `view_state` should be offset.
+    pub fn is_enabled(&self, view_state: &ViewState) -> bool {
+        self.mode != Mode::Off && are_poses_valid(view_state)
+    }
+
+    /// Move `view` to the pupil of the eye, `gaze` is the eye rotation relative to the view.
+    pub fn apply(&self, view: &mut Posef, gaze: Quaternion<f32>, is_left: bool) {
+        let orientation = pose::from_quaternionf(view.orientation);
+        let position = pupil_position(
+            pose::from_vector3f(view.position),
+            orientation,
+            gaze,
+            self.radii[if is_left { 0 } else { 1 }],
+        );
+        view.position = pose::to_vector3f(position);
+
+        if self.mode == Mode::PositionRotation {
+            let turn = quat::slerp(IDENTITY, gaze, self.rotation_fraction);
+            view.orientation = pose::to_quaternionf(quat::mul(orientation, turn));
+        }
+    }
+}
+
+const IDENTITY: Quaternion<f32> = (1.0, [0.0; 3]);
+
+/// Whether the runtime returned usable view poses.
+pub fn are_poses_valid(view_state: &ViewState) -> bool {
+    view_state
+        .view_state_flags
+        .contains(ViewStateFlags::POSITION_VALID | ViewStateFlags::ORIENTATION_VALID)
+}
+
+/// For each view of a view configuration, whether it belongs to the left eye, `None` for view
+/// configurations without eyes.
+pub fn view_layout(view_configuration_type: ViewConfigurationType) -> Option<&'static [bool]> {
+    let ty = view_configuration_type;
+    if ty == ViewConfigurationType::PRIMARY_STEREO {
+        Some(&[true, false])
+    } else if ty == ViewConfigurationType::PRIMARY_QUAD_VARJO {
+        // Also `PRIMARY_STEREO_WITH_FOVEATED_INSET` from OpenXR 1.1, which is an alias with the
+        // same value.
+        // Left and right context views, then left and right inset views.
+        Some(&[true, false, true, false])
+    } else {
+        None
+    }
+}
+
+/// Typical distance from the rotation centre of the eye to its entrance pupil.
+const DEFAULT_RADIUS_MM: f32 = 10.5;
+
+/// Position of the pupil of an eye rotated by `gaze` relative to a view at `view_position` and
+/// `view_orientation`, with the pupil `radius` metres from the rotation centre.
+pub fn pupil_position(
+    view_position: Vector3<f32>,
+    view_orientation: Quaternion<f32>,
+    gaze: Quaternion<f32>,
+    radius: f32,
+) -> Vector3<f32> {
+    let to_pupil = pose::FORWARD.scale(radius);
+    let center = view_position.sub(quat::point_rotation(view_orientation, to_pupil));
+    let eye = quat::mul(view_orientation, gaze);
+    center.add(quat::point_rotation(eye, to_pupil))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-6;
+    const RADIUS: f32 = 0.0105;
+
+    fn assert_vec_eq(a: Vector3<f32>, b: Vector3<f32>) {
+        let distance = quat::norm(a.sub(b));
+        assert!(distance < EPSILON, "{a:?} != {b:?}");
+    }
+
+    fn gaze(pitch_deg: f32, yaw_deg: f32) -> Quaternion<f32> {
+        pose::look_rotation(
+            pose::direction_from_angles(pitch_deg.to_radians(), yaw_deg.to_radians()),
+            0.0,
+        )
+    }
+
+    #[test]
+    fn looking_ahead_keeps_view_position() {
+        let position = [0.032, 1.6, -0.1];
+        let orientation = quat::from_axis_angle([0.0, 1.0, 0.0], 0.7);
+        assert_vec_eq(
+            pupil_position(position, orientation, IDENTITY, RADIUS),
+            position,
+        );
+    }
+
+    #[test]
+    fn looking_up_moves_pupil_up_and_back() {
+        let angle = 30f32.to_radians();
+        let pupil = pupil_position([0.0; 3], IDENTITY, gaze(30.0, 0.0), RADIUS);
+        assert_vec_eq(
+            pupil,
+            [0.0, RADIUS * angle.sin(), RADIUS * (1.0 - angle.cos())],
+        );
+    }
+
+    #[test]
+    fn looking_left_moves_pupil_left_and_back() {
+        let pupil = pupil_position([0.0; 3], IDENTITY, gaze(0.0, 90.0), RADIUS);
+        assert_vec_eq(pupil, [-RADIUS, 0.0, RADIUS]);
+    }
+
+    #[test]
+    fn offset_follows_view_orientation() {
+        // Head turned 90° to the left, so the view looks down -X and its left is +Z.
+        let orientation = quat::from_axis_angle([0.0, 1.0, 0.0], 90f32.to_radians());
+        let position = [0.1, 1.5, 0.2];
+        let pupil = pupil_position(position, orientation, gaze(0.0, 90.0), RADIUS);
+        assert_vec_eq(pupil, position.add([RADIUS, 0.0, RADIUS]));
+    }
+
+    #[test]
+    fn torsion_doesnt_move_pupil() {
+        let direction = pose::direction_from_angles(0.2, -0.3);
+        let without = pupil_position(
+            [0.0; 3],
+            IDENTITY,
+            pose::look_rotation(direction, 0.0),
+            RADIUS,
+        );
+        let with = pupil_position(
+            [0.0; 3],
+            IDENTITY,
+            pose::look_rotation(direction, 0.4),
+            RADIUS,
+        );
+        assert_vec_eq(with, without);
+    }
+
+    #[test]
+    fn pupil_stays_on_sphere_around_center() {
+        let center = [0.0, 0.0, RADIUS];
+        for (pitch, yaw) in [(10.0, 20.0), (-25.0, 5.0), (0.0, -40.0)] {
+            let pupil = pupil_position([0.0; 3], IDENTITY, gaze(pitch, yaw), RADIUS);
+            assert!((quat::norm(pupil.sub(center)) - RADIUS).abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn quad_views_alternate_eyes() {
+        assert_eq!(
+            view_layout(ViewConfigurationType::PRIMARY_STEREO),
+            Some(&[true, false][..])
+        );
+        assert_eq!(
+            view_layout(ViewConfigurationType::PRIMARY_QUAD_VARJO),
+            Some(&[true, false, true, false][..])
+        );
+        assert_eq!(view_layout(ViewConfigurationType::PRIMARY_MONO), None);
+    }
+}
@@ -0,0 +1,100 @@
+//! Rust binding for the `XR_ETVR_raw_eye_data` vendor extension, see
+//! `include/XR_ETVR_raw_eye_data.h` for the documentation.
+
+use std::ffi::c_void;
+
+use openxr_sys::{Bool32, Result, Session, StructureType, Time, Vector3f};
+
+pub const EXTENSION_NAME: &str = "XR_ETVR_raw_eye_data";
+pub const SPEC_VERSION: u32 = 4;
+
+pub const TYPE_RAW_EYE_DATA_GET_INFO_ETVR: i32 = 1999000000;
+pub const TYPE_RAW_EYE_DATA_ETVR: i32 = 1999000001;
+pub const TYPE_GAZE_CLASSIFICATION_ETVR: i32 = 1999000002;
+pub const TYPE_FIXATION_POINT_ETVR: i32 = 1999000003;
+pub const TYPE_EYE_TORSION_ETVR: i32 = 1999000004;
+
+pub type RawEyeStateFlagsETVR = u64;
+
+pub const RAW_EYE_STATE_GAZE_VALID_BIT_ETVR: RawEyeStateFlagsETVR = 0x00000001;
+pub const RAW_EYE_STATE_OPENNESS_VALID_BIT_ETVR: RawEyeStateFlagsETVR = 0x00000002;
+pub const RAW_EYE_STATE_PUPIL_DIAMETER_VALID_BIT_ETVR: RawEyeStateFlagsETVR = 0x00000004;
+pub const RAW_EYE_STATE_CONFIDENCE_VALID_BIT_ETVR: RawEyeStateFlagsETVR = 0x00000008;
+
+pub const RAW_EYE_LEFT_ETVR: usize = 0;
+pub const RAW_EYE_RIGHT_ETVR: usize = 1;
+pub const RAW_EYE_COUNT_ETVR: usize = 2;
+
+pub type GazeEventETVR = i32;
+
+pub const GAZE_EVENT_FIXATION_ETVR: GazeEventETVR = 0;
+pub const GAZE_EVENT_SACCADE_ETVR: GazeEventETVR = 1;
+pub const GAZE_EVENT_BLINK_ETVR: GazeEventETVR = 2;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct RawEyeDataGetInfoETVR {
+    pub ty: StructureType,
+    pub next: *const c_void,
+    pub time: Time,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RawEyeStateETVR {
+    pub flags: RawEyeStateFlagsETVR,
+    pub pitch: f32,
+    pub yaw: f32,
+    pub openness: f32,
+    pub widen: f32,
+    pub squeeze: f32,
+    pub pupil_diameter: f32,
+    pub confidence: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct RawEyeDataETVR {
+    pub ty: StructureType,
+    pub next: *mut c_void,
+    pub sample_time: Time,
+    pub sender_time: Time,
+    pub eyes: [RawEyeStateETVR; RAW_EYE_COUNT_ETVR],
+}
+
+/// Since spec version 2, chained to `RawEyeDataETVR::next`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct GazeClassificationETVR {
+    pub ty: StructureType,
+    pub next: *mut c_void,
+    pub event: GazeEventETVR,
+    pub angular_velocity: f32,
+}
+
+/// Since spec version 3, chained to `RawEyeDataETVR::next`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct FixationPointETVR {
+    pub ty: StructureType,
+    pub next: *mut c_void,
+    pub position: Vector3f,
+    pub distance: f32,
+    pub confidence: f32,
+}
+
+/// Since spec version 4, chained to `RawEyeDataETVR::next`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct EyeTorsionETVR {
+    pub ty: StructureType,
+    pub next: *mut c_void,
+    pub torsion: [f32; RAW_EYE_COUNT_ETVR],
+    pub is_torsion_valid: [Bool32; RAW_EYE_COUNT_ETVR],
+}
+
+pub type GetRawEyeDataETVR = unsafe extern "system" fn(
+    session: Session,
+    get_info: *const RawEyeDataGetInfoETVR,
+    eye_data: *mut RawEyeDataETVR,
+) -> Result;
@@ -0,0 +1,170 @@
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use openxr_sys::Action;
+
+use crate::config::Config;
+
+/// Detects the controller chord that recenters the gaze, from the app's own boolean actions.
+///
+/// The chord is a list of binding paths in `recenter.chord`. Only buttons the app binds to an
+/// action can be detected, and only while the app reads the state of those actions. Button
+/// states are recorded as the app reads them, and the hold time is checked once per frame.
+pub struct RecenterChord {
+    paths: Vec<String>,
+    hold_time: Duration,
+    /// Index into `paths` of the actions bound to them.
+    actions: HashMap<Action, usize>,
+    pressed: Vec<AtomicBool>,
+}
+
+/// How long the chord has been held, kept by the frame loop.
+#[derive(Debug, Default)]
+pub struct ChordHold {
+    held_since: Option<Instant>,
+    /// Set once the chord has fired, until it's released.
+    has_fired: bool,
+}
+
+impl RecenterChord {
+    pub fn new(config: &Config) -> RecenterChord {
+        let paths: Vec<String> = config
+            .get_str("recenter.chord")
+            .unwrap_or("")
+            .split(',')
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty())
+            .collect();
+
+        RecenterChord {
+            pressed: paths.iter().map(|_| AtomicBool::new(false)).collect(),
+            paths,
+            hold_time: Duration::from_secs_f32(config.get("recenter.hold_time", 1.0)),
+            actions: HashMap::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.paths.is_empty()
+    }
+
+    /// Remember `action` if the app suggested it for one of the chord buttons.
+    pub fn add_binding(&mut self, action: Action, binding: &str) {
+        if let Some(index) = self.paths.iter().position(|p| p == binding) {
+            println!("Recenter chord button {binding} bound to {:?}", action);
+            self.actions.insert(action, index);
+        }
+    }
+
+    /// Record the state of `action`, if it's one of the chord buttons.
+    pub fn set_pressed(&self, action: Action, is_pressed: bool) {
+        if let Some(&index) = self.actions.get(&action) {
+            self.pressed[index].store(is_pressed, Ordering::Relaxed);
+        }
+    }
+
+    /// Check the chord at `now`, returns true once when it has been held long enough.
+    pub fn update(&self, hold: &mut ChordHold, now: Instant) -> bool {
+        let is_held = self.is_enabled() && self.pressed.iter().all(|p| p.load(Ordering::Relaxed));
+        if !is_held {
+            *hold = ChordHold::default();
+            return false;
+        }
+
+        let held_since = *hold.held_since.get_or_insert(now);
+        if hold.has_fired || now.duration_since(held_since) < self.hold_time {
+            return false;
+        }
+
+        hold.has_fired = true;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A: &str = "/user/hand/left/input/x/click";
+    const B: &str = "/user/hand/right/input/a/click";
+
+    fn chord() -> RecenterChord {
+        let mut chord = RecenterChord::new(&Config::parse(&format!(
+            "[recenter]\nchord = {A}, {B}\nhold_time = 1"
+        )));
+        chord.add_binding(Action::from_raw(1), A);
+        chord.add_binding(Action::from_raw(2), B);
+        chord.add_binding(Action::from_raw(3), "/user/hand/right/input/b/click");
+        chord
+    }
+
+    fn press(chord: &RecenterChord, is_pressed: bool) {
+        chord.set_pressed(Action::from_raw(1), is_pressed);
+        chord.set_pressed(Action::from_raw(2), is_pressed);
+    }
+
+    fn seconds(s: f32) -> Duration {
+        Duration::from_secs_f32(s)
+    }
+
+    #[test]
+    fn fires_once_after_the_hold_time() {
+        let chord = chord();
+        let mut hold = ChordHold::default();
+        let start = Instant::now();
+
+        press(&chord, true);
+        assert!(!chord.update(&mut hold, start));
+        assert!(!chord.update(&mut hold, start + seconds(0.9)));
+        assert!(chord.update(&mut hold, start + seconds(1.0)));
+        assert!(!chord.update(&mut hold, start + seconds(1.5)));
+        assert!(!chord.update(&mut hold, start + seconds(5.0)));
+    }
+
+    #[test]
+    fn release_restarts_the_hold() {
+        let chord = chord();
+        let mut hold = ChordHold::default();
+        let start = Instant::now();
+
+        press(&chord, true);
+        assert!(!chord.update(&mut hold, start));
+        chord.set_pressed(Action::from_raw(2), false);
+        assert!(!chord.update(&mut hold, start + seconds(0.5)));
+        chord.set_pressed(Action::from_raw(2), true);
+        assert!(!chord.update(&mut hold, start + seconds(0.6)));
+        assert!(!chord.update(&mut hold, start + seconds(1.5)));
+        assert!(chord.update(&mut hold, start + seconds(1.6)));
+
+        // Fires again after a release.
+        press(&chord, false);
+        assert!(!chord.update(&mut hold, start + seconds(2.0)));
+        press(&chord, true);
+        assert!(!chord.update(&mut hold, start + seconds(2.1)));
+        assert!(chord.update(&mut hold, start + seconds(3.1)));
+    }
+
+    #[test]
+    fn needs_every_button() {
+        let chord = chord();
+        let mut hold = ChordHold::default();
+        let start = Instant::now();
+
+        chord.set_pressed(Action::from_raw(1), true);
+        chord.set_pressed(Action::from_raw(3), true);
+        assert!(!chord.update(&mut hold, start));
+        assert!(!chord.update(&mut hold, start + seconds(2.0)));
+    }
+
+    #[test]
+    fn empty_chord_never_fires() {
+        let chord = RecenterChord::new(&Config::parse(""));
+        let mut hold = ChordHold::default();
+        let start = Instant::now();
+        assert!(!chord.update(&mut hold, start));
+        assert!(!chord.update(&mut hold, start + seconds(10.0)));
+    }
+}
@@ -0,0 +1,169 @@
+use openxr_sys::Path;
+use quaternion_core::Vector3;
+
+use super::{Eyes, GazeStore, StoreSource};
+use crate::{config::Config, pose};
+
+/// Drives the gaze with a controller thumbstick, for developing without a tracker.
+///
+/// The stick is read from the app's own action bound to `controller.thumbstick`, so it only
+/// moves the gaze while the app reads that action. Pushing the stick all the way looks
+/// `controller.range` degrees away from straight ahead, and clicking it, if the app binds the
+/// click, closes the eyes.
+///
+/// The layer keeps a handle to the source to feed it the app's action states.
+#[derive(Clone)]
+pub struct ControllerSource {
+    store: GazeStore,
+    binding: String,
+    /// Top level user path of the thumbstick, like `/user/hand/right`.
+    hand: String,
+    /// Radians.
+    range: f32,
+}
+
+impl ControllerSource {
+    pub fn start(config: &Config) -> ControllerSource {
+        let binding =
+            config.get_string("controller.thumbstick", "/user/hand/right/input/thumbstick");
+        println!("Gaze driven by {binding}");
+        let hand = hand_of(&binding).to_string();
+
+        ControllerSource {
+            store: GazeStore::new(config),
+            binding,
+            hand,
+            range: config.get::<f32>("controller.range", 30.0).to_radians(),
+        }
+    }
+
+    /// Binding path of the thumbstick.
+    pub fn binding(&self) -> &str {
+        &self.binding
+    }
+
+    /// Top level user path the thumbstick belongs to.
+    pub fn hand(&self) -> &str {
+        &self.hand
+    }
+
+    /// Position of the thumbstick, -1 to 1 with +x right and +y up.
+    pub fn update_thumbstick(&self, x: f32, y: f32) {
+        let direction = thumbstick_direction(x, y, self.range);
+        self.store.update_gaze(None, Eyes::Both, |d| {
+            d.l_direction = direction;
+            d.r_direction = direction;
+            d.l_confidence = Some(1.0);
+            d.r_confidence = Some(1.0);
+        });
+    }
+
+    pub fn update_thumbstick_click(&self, is_pressed: bool) {
+        let openness = click_openness(is_pressed);
+        self.store.update_eyes(|d| {
+            d.l_openness = openness;
+            d.r_openness = openness;
+        });
+    }
+}
+
+/// Top level user path of `binding`, like `/user/hand/right` for
+/// `/user/hand/right/input/thumbstick`.
+fn hand_of(binding: &str) -> &str {
+    binding
+        .split_once("/input/")
+        .map_or(binding, |(hand, _)| hand)
+}
+
+/// Gaze direction with the thumbstick at `x`, `y`, turning up to `range` radians.
+fn thumbstick_direction(x: f32, y: f32, range: f32) -> Vector3<f32> {
+    // Stick right looks right, which is negative yaw.
+    pose::direction_from_angles(y * range, -x * range)
+}
+
+/// Eye openness with the thumbstick clicked or not.
+fn click_openness(is_pressed: bool) -> f32 {
+    if is_pressed {
+        0.0
+    } else {
+        1.0
+    }
+}
+
+/// Whether an action state queried for `subaction_path` belongs to the thumbstick `hand`. The
+/// null path queries all hands.
+pub fn is_thumbstick_hand(subaction_path: Path, hand: Option<Path>) -> bool {
+    subaction_path == Path::NULL || Some(subaction_path) == hand
+}
+
+impl StoreSource for ControllerSource {
+    fn store(&self) -> &GazeStore {
+        &self.store
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_direction(actual: Vector3<f32>, expected: Vector3<f32>) {
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-5, "{actual:?} isn't {expected:?}");
+        }
+    }
+
+    #[test]
+    fn centred_stick_looks_forward() {
+        assert_direction(thumbstick_direction(0.0, 0.0, 0.5), pose::FORWARD);
+    }
+
+    #[test]
+    fn stick_turns_the_gaze_by_the_range() {
+        let range = 30f32.to_radians();
+        let (sin, cos) = range.sin_cos();
+        // Right, left, up and down.
+        let cases = [
+            ((1.0, 0.0), [sin, 0.0, -cos]),
+            ((-1.0, 0.0), [-sin, 0.0, -cos]),
+            ((0.0, 1.0), [0.0, sin, -cos]),
+            ((0.0, -1.0), [0.0, -sin, -cos]),
+        ];
+        for ((x, y), expected) in cases {
+            assert_direction(thumbstick_direction(x, y, range), expected);
+        }
+
+        let (pitch, yaw) = pose::angles_from_direction(thumbstick_direction(0.5, -0.5, range));
+        assert!((pitch + range / 2.0).abs() < 1e-5);
+        assert!((yaw + range / 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn click_closes_the_eyes() {
+        assert_eq!(click_openness(true), 0.0);
+        assert_eq!(click_openness(false), 1.0);
+    }
+
+    #[test]
+    fn hand_is_the_binding_prefix() {
+        assert_eq!(
+            hand_of("/user/hand/right/input/thumbstick"),
+            "/user/hand/right"
+        );
+        assert_eq!(
+            hand_of("/user/gamepad/input/thumbstick_left"),
+            "/user/gamepad"
+        );
+        assert_eq!(hand_of("/user/hand/left"), "/user/hand/left");
+    }
+
+    #[test]
+    fn null_subaction_matches_any_hand() {
+        let left = Path::from_raw(1);
+        let right = Path::from_raw(2);
+        assert!(is_thumbstick_hand(Path::NULL, Some(right)));
+        assert!(is_thumbstick_hand(Path::NULL, None));
+        assert!(is_thumbstick_hand(right, Some(right)));
+        assert!(!is_thumbstick_hand(left, Some(right)));
+        assert!(!is_thumbstick_hand(right, None));
+    }
+}
@@ -0,0 +1,72 @@
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+#[cfg(target_os = "android")]
+use std::os::android::net::SocketAddrExt;
+#[cfg(target_os = "linux")]
+use std::os::linux::net::SocketAddrExt;
+
+use super::{osc::Receiver, GazeStore, StoreSource};
+use crate::config::Config;
+
+/// Receives the same OSC packets as `OSCServer`, but over a local Unix datagram socket so a
+/// tracker running on the headset doesn't need the network.
+///
+/// Socket paths starting with `@` are in the abstract namespace, which apps can bind to without
+/// any file system access.
+pub struct IpcSource {
+    store: GazeStore,
+}
+
+impl IpcSource {
+    pub fn start(config: &Config) -> IpcSource {
+        let source = IpcSource {
+            store: GazeStore::new(config),
+        };
+
+        let path = config.get_string("ipc.path", "@etvr-openxr-layer");
+        let receiver = Receiver::new(config, source.store.clone());
+
+        let socket = match bind(&path) {
+            Ok(socket) => socket,
+            Err(e) => {
+                println!("Can't bind IPC socket {path}: {e}");
+                return source;
+            }
+        };
+
+        std::thread::spawn(move || {
+            println!("IPC socket loop on {path}");
+            let mut buf = [0; rosc::decoder::MTU];
+            loop {
+                match socket.recv(&mut buf) {
+                    Ok(size) => receiver.handle_datagram(&buf[..size]),
+                    Err(e) => {
+                        println!("Error receiving from IPC socket: {}", e);
+                        break;
+                    }
+                }
+            }
+        });
+
+        source
+    }
+}
+
+fn bind(path: &str) -> std::io::Result<UnixDatagram> {
+    let addr = match path.strip_prefix('@') {
+        Some(name) => SocketAddr::from_abstract_name(name)?,
+        None => {
+            // A socket file left over from a previous run would make the bind fail.
+            let _ = std::fs::remove_file(path);
+            SocketAddr::from_pathname(path)?
+        }
+    };
+
+    UnixDatagram::bind_addr(&addr)
+}
+
+impl StoreSource for IpcSource {
+    fn store(&self) -> &GazeStore {
+        &self.store
+    }
+}
@@ -0,0 +1,341 @@
+//! Where the layer gets eye and face data from.
+//!
+//! The hooks only see the [`GazeSource`] trait, the source itself is picked with the top level
+//! `source` config key.
+
+mod controller;
+mod ipc;
+mod osc;
+mod recording;
+mod replay;
+mod synthetic;
+
+use std::{
+    path::Path,
+    sync::{mpsc::Sender, Arc, Mutex},
+    time::{Instant, SystemTime},
+};
+
+use quaternion_core::Vector3;
+
+use crate::{config::Config, face::mouth, latch::Latch, pose};
+
+pub use self::{
+    controller::{is_thumbstick_hand, ControllerSource},
+    ipc::IpcSource,
+    osc::OSCServer,
+    replay::ReplaySource,
+    synthetic::SyntheticSource,
+};
+
+/// Number of gaze samples kept in `GazeStore::eye_gaze_history`.
+pub const EYE_GAZE_HISTORY_SIZE: usize = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EyeGazeData {
+    /// Unit gaze direction of each eye in OpenXR conventions, -Z forward, +Y up and +X right.
+    pub l_direction: Vector3<f32>,
+    pub r_direction: Vector3<f32>,
+    /// Ocular torsion in radians, counter-clockwise from the user's point of view, if the tracker
+    /// sends it.
+    pub l_torsion: Option<f32>,
+    pub r_torsion: Option<f32>,
+    /// Eyelid openness, 0 is closed and 1 is fully open.
+    pub l_openness: f32,
+    pub r_openness: f32,
+    /// How much the eyes are opened wider than normal, 0 to 1.
+    pub l_widen: f32,
+    pub r_widen: f32,
+    /// How much the eyes are squinting, 0 to 1.
+    pub l_squeeze: f32,
+    pub r_squeeze: f32,
+    /// Tracker confidence in the gaze, 0 to 1, if the tracker sends it.
+    pub l_confidence: Option<f32>,
+    pub r_confidence: Option<f32>,
+    /// Pupil diameter in millimetres, if the tracker sends it.
+    pub l_pupil_diameter: Option<f32>,
+    pub r_pupil_diameter: Option<f32>,
+    /// When the pupil diameter of each eye was last received.
+    pub l_pupil_time: SystemTime,
+    pub r_pupil_time: SystemTime,
+    /// When the gaze of each eye was last received.
+    pub l_time: SystemTime,
+    pub r_time: SystemTime,
+    /// When the gaze was received.
+    pub time: SystemTime,
+    /// When the tracker sent the gaze, e.g. the time tag of the OSC bundle it arrived in.
+    pub sender_time: Option<SystemTime>,
+    /// When the gaze was received on the monotonic clock, `None` before the first sample.
+    pub received: Option<Instant>,
+}
+
+impl Default for EyeGazeData {
+    fn default() -> Self {
+        EyeGazeData {
+            l_direction: pose::FORWARD,
+            r_direction: pose::FORWARD,
+            l_torsion: None,
+            r_torsion: None,
+            l_openness: 1.0,
+            r_openness: 1.0,
+            l_widen: 0.0,
+            r_widen: 0.0,
+            l_squeeze: 0.0,
+            r_squeeze: 0.0,
+            l_confidence: None,
+            r_confidence: None,
+            l_pupil_diameter: None,
+            r_pupil_diameter: None,
+            l_pupil_time: SystemTime::UNIX_EPOCH,
+            r_pupil_time: SystemTime::UNIX_EPOCH,
+            l_time: SystemTime::UNIX_EPOCH,
+            r_time: SystemTime::UNIX_EPOCH,
+            time: SystemTime::UNIX_EPOCH,
+            sender_time: None,
+            received: None,
+        }
+    }
+}
+
+/// Lower face expressions, indexed by the `face::mouth` constants.
+#[derive(Debug, Clone, Copy)]
+pub struct MouthData {
+    pub weights: [f32; mouth::COUNT],
+    pub time: SystemTime,
+    /// When the expressions were received on the monotonic clock, `None` before the first ones.
+    pub received: Option<Instant>,
+}
+
+impl Default for MouthData {
+    fn default() -> Self {
+        MouthData {
+            weights: [0.0; mouth::COUNT],
+            time: SystemTime::UNIX_EPOCH,
+            received: None,
+        }
+    }
+}
+
+/// A running producer of eye and face data.
+pub trait GazeSource: Send + Sync {
+    /// The latest gaze sample.
+    fn eye_gaze_data(&self) -> EyeGazeData;
+    /// The latest gaze samples, oldest first.
+    fn eye_gaze_history(&self) -> Vec<EyeGazeData>;
+    /// The latest lower face expressions.
+    fn mouth_data(&self) -> MouthData;
+    /// Control commands received since the last call.
+    fn take_commands(&self) -> Vec<Command>;
+}
+
+/// A source that publishes everything through a `GazeStore`, which answers its `GazeSource`
+/// queries.
+pub trait StoreSource: Send + Sync {
+    fn store(&self) -> &GazeStore;
+}
+
+impl<T: StoreSource> GazeSource for T {
+    fn eye_gaze_data(&self) -> EyeGazeData {
+        self.store().eye_gaze_data()
+    }
+
+    fn eye_gaze_history(&self) -> Vec<EyeGazeData> {
+        self.store().eye_gaze_history()
+    }
+
+    fn mouth_data(&self) -> MouthData {
+        self.store().mouth_data()
+    }
+
+    fn take_commands(&self) -> Vec<Command> {
+        self.store().take_commands()
+    }
+}
+
+/// Requests to the layer that arrive alongside the data, e.g. as OSC control messages.
+#[derive(Debug, Clone)]
+pub enum Command {
+    /// Forget collected calibration targets and start collecting new ones, optionally with a
+    /// different calibration model.
+    StartCalibration {
+        model: Option<String>,
+    },
+    /// The user is looking at this direction, in radians.
+    CalibrationTarget {
+        pitch: f32,
+        yaw: f32,
+    },
+    /// Fit the collected targets, then save and apply them as a profile.
+    FinishCalibration {
+        profile: String,
+    },
+    LoadCalibration {
+        profile: String,
+    },
+    ClearCalibration,
+    /// Take the current gaze as looking straight ahead.
+    Recenter,
+}
+
+/// Create and start the source selected in the config. The controller source is also returned
+/// as itself, since the layer feeds it the thumbstick.
+pub fn from_config(config: &Config) -> (Box<dyn GazeSource>, Option<ControllerSource>) {
+    match config.get_str("source") {
+        Some("osc") | None => (Box::new(OSCServer::start(config)), None),
+        Some("ipc") => (Box::new(IpcSource::start(config)), None),
+        Some("synthetic") => (Box::new(SyntheticSource::start(config)), None),
+        Some("replay") => (Box::new(ReplaySource::start(config)), None),
+        Some("controller") => {
+            let source = ControllerSource::start(config);
+            (Box::new(source.clone()), Some(source))
+        }
+        Some(source) => {
+            println!("Unknown gaze source {source}, using osc");
+            (Box::new(OSCServer::start(config)), None)
+        }
+    }
+}
+
+/// Which eyes a gaze update is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Eyes {
+    Left,
+    Right,
+    Both,
+}
+
+/// Latest data shared between a source's threads and the hooks.
+///
+/// The gaze and mouth data are published through latches, so the hooks on the render thread read
+/// them without ever waiting for a receiver thread.
+#[derive(Clone)]
+pub struct GazeStore {
+    eye_gaze_data: Arc<Latch<EyeGazeData>>,
+    eye_gaze_history: Arc<Latch<History>>,
+    mouth_data: Arc<Latch<MouthData>>,
+    commands: Arc<Mutex<Vec<Command>>>,
+    /// Every new gaze sample is sent here if `record.path` is set.
+    recorder: Option<Sender<EyeGazeData>>,
+}
+
+impl GazeStore {
+    pub fn new(config: &Config) -> GazeStore {
+        let recorder = config.get_str("record.path").and_then(|path| {
+            recording::start_recording(Path::new(path))
+                .inspect_err(|e| println!("Can't record gaze to {path}: {e}"))
+                .ok()
+        });
+
+        GazeStore {
+            eye_gaze_data: Arc::new(Latch::new(EyeGazeData::default())),
+            eye_gaze_history: Arc::new(Latch::new(History::new())),
+            mouth_data: Arc::new(Latch::new(MouthData::default())),
+            commands: Arc::new(Mutex::new(Vec::new())),
+            recorder,
+        }
+    }
+
+    /// Apply a new gaze sample for `eyes` and record it in the history.
+    pub fn update_gaze(
+        &self,
+        sender_time: Option<SystemTime>,
+        eyes: Eyes,
+        update: impl FnOnce(&mut EyeGazeData),
+    ) {
+        let eye_gaze_data = self.eye_gaze_data.update(|eye_gaze_data| {
+            update(eye_gaze_data);
+            let now = SystemTime::now();
+            eye_gaze_data.time = now;
+            eye_gaze_data.sender_time = sender_time;
+            eye_gaze_data.received = Some(Instant::now());
+            if eyes != Eyes::Right {
+                eye_gaze_data.l_time = now;
+            }
+            if eyes != Eyes::Left {
+                eye_gaze_data.r_time = now;
+            }
+            *eye_gaze_data
+        });
+
+        self.eye_gaze_history
+            .update(|history| history.push(eye_gaze_data));
+
+        if let Some(recorder) = &self.recorder {
+            let _ = recorder.send(eye_gaze_data);
+        }
+    }
+
+    /// Change values that arrive separately from the gaze, like eyelids, without making a new
+    /// sample.
+    pub fn update_eyes(&self, update: impl FnOnce(&mut EyeGazeData)) {
+        self.eye_gaze_data.update(update);
+    }
+
+    pub fn update_mouth(&self, update: impl FnOnce(&mut MouthData)) {
+        self.mouth_data.update(|mouth_data| {
+            update(mouth_data);
+            mouth_data.time = SystemTime::now();
+            mouth_data.received = Some(Instant::now());
+        });
+    }
+
+    pub fn push_command(&self, command: Command) {
+        self.commands.lock().unwrap().push(command);
+    }
+
+    /// Commands pushed so far. If a receiver is pushing one right now they're left for the next
+    /// call instead of waiting for it.
+    pub fn take_commands(&self) -> Vec<Command> {
+        match self.commands.try_lock() {
+            Ok(mut commands) => std::mem::take(&mut commands),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    pub fn eye_gaze_data(&self) -> EyeGazeData {
+        self.eye_gaze_data.read()
+    }
+
+    pub fn eye_gaze_history(&self) -> Vec<EyeGazeData> {
+        self.eye_gaze_history.read().samples()
+    }
+
+    pub fn mouth_data(&self) -> MouthData {
+        self.mouth_data.read()
+    }
+}
+
+/// The latest `EYE_GAZE_HISTORY_SIZE` gaze samples in a ring buffer, so they can be copied out of
+/// a latch.
+#[derive(Clone, Copy)]
+pub struct History {
+    samples: [EyeGazeData; EYE_GAZE_HISTORY_SIZE],
+    /// Where the next sample goes, the oldest sample once the buffer is full.
+    next: usize,
+    len: usize,
+}
+
+impl History {
+    pub fn new() -> History {
+        History {
+            samples: [EyeGazeData::default(); EYE_GAZE_HISTORY_SIZE],
+            next: 0,
+            len: 0,
+        }
+    }
+
+    pub fn push(&mut self, sample: EyeGazeData) {
+        self.samples[self.next] = sample;
+        self.next = (self.next + 1) % EYE_GAZE_HISTORY_SIZE;
+        self.len = (self.len + 1).min(EYE_GAZE_HISTORY_SIZE);
+    }
+
+    /// The samples, oldest first.
+    pub fn samples(&self) -> Vec<EyeGazeData> {
+        if self.len < EYE_GAZE_HISTORY_SIZE {
+            self.samples[..self.len].to_vec()
+        } else {
+            [&self.samples[self.next..], &self.samples[..self.next]].concat()
+        }
+    }
+}
@@ -0,0 +1,498 @@
+use std::{net::UdpSocket, time::SystemTime};
+
+use rosc::{OscMessage, OscPacket, OscTime};
+
+use super::{Command, EyeGazeData, Eyes, GazeStore, StoreSource};
+use crate::{config::Config, face::mouth, pose};
+
+/// OSC addresses of the per-eye values that are sent as separate messages.
+#[derive(Debug, Clone)]
+struct EyeAddresses {
+    l_pitch_yaw: String,
+    r_pitch_yaw: String,
+    l_openness: String,
+    r_openness: String,
+    l_widen: String,
+    r_widen: String,
+    l_squeeze: String,
+    r_squeeze: String,
+    l_confidence: String,
+    r_confidence: String,
+    l_pupil_diameter: String,
+    r_pupil_diameter: String,
+    l_torsion: String,
+    r_torsion: String,
+}
+
+impl EyeAddresses {
+    fn from_config(config: &Config) -> EyeAddresses {
+        EyeAddresses {
+            l_pitch_yaw: config.get_string("osc.left_pitch_yaw", "/tracking/eye/LeftPitchYaw"),
+            r_pitch_yaw: config.get_string("osc.right_pitch_yaw", "/tracking/eye/RightPitchYaw"),
+            l_openness: config.get_string("osc.left_openness", "/avatar/parameters/LeftEyeLid"),
+            r_openness: config.get_string("osc.right_openness", "/avatar/parameters/RightEyeLid"),
+            l_widen: config.get_string("osc.left_widen", "/avatar/parameters/v2/EyeWideLeft"),
+            r_widen: config.get_string("osc.right_widen", "/avatar/parameters/v2/EyeWideRight"),
+            l_squeeze: config.get_string("osc.left_squeeze", "/avatar/parameters/v2/EyeSquintLeft"),
+            r_squeeze: config
+                .get_string("osc.right_squeeze", "/avatar/parameters/v2/EyeSquintRight"),
+            l_confidence: config.get_string("osc.left_confidence", ""),
+            r_confidence: config.get_string("osc.right_confidence", ""),
+            l_pupil_diameter: config
+                .get_string("osc.left_pupil_diameter", "/tracking/eye/LeftPupilDiameter"),
+            r_pupil_diameter: config.get_string(
+                "osc.right_pupil_diameter",
+                "/tracking/eye/RightPupilDiameter",
+            ),
+            l_torsion: config.get_string("osc.left_torsion", ""),
+            r_torsion: config.get_string("osc.right_torsion", ""),
+        }
+    }
+}
+
+/// How the tracker reports pupil diameter.
+#[derive(Debug, Clone, Copy)]
+enum PupilUnits {
+    Millimetres,
+    /// 0 to 1, mapped linearly to the given diameter range in millimetres.
+    Normalized {
+        min_mm: f32,
+        max_mm: f32,
+    },
+}
+
+impl PupilUnits {
+    fn from_config(config: &Config) -> PupilUnits {
+        match config.get_str("osc.pupil_units") {
+            Some("normalized") => PupilUnits::Normalized {
+                min_mm: config.get("osc.pupil_min_mm", 2.0),
+                max_mm: config.get("osc.pupil_max_mm", 8.0),
+            },
+            Some("mm") | None => PupilUnits::Millimetres,
+            Some(units) => {
+                println!("Unknown pupil units {units}, using millimetres");
+                PupilUnits::Millimetres
+            }
+        }
+    }
+
+    fn to_millimetres(self, value: f32) -> f32 {
+        match self {
+            PupilUnits::Millimetres => value,
+            PupilUnits::Normalized { min_mm, max_mm } => {
+                min_mm + value.clamp(0.0, 1.0) * (max_mm - min_mm)
+            }
+        }
+    }
+}
+
+/// Receives OSC messages from the eye tracker and Project Babble over UDP.
+pub struct OSCServer {
+    store: GazeStore,
+    port: u16,
+    /// Extra port to listen on if the mouth tracker can't send to `port`.
+    mouth_port: Option<u16>,
+}
+
+impl OSCServer {
+    pub fn start(config: &Config) -> OSCServer {
+        let server = OSCServer {
+            store: GazeStore::new(config),
+            port: config.get("osc.port", 9000),
+            mouth_port: config
+                .get_str("osc.mouth_port")
+                .and_then(|p| p.parse().ok()),
+        };
+
+        server.run(Receiver::new(config, server.store.clone()));
+
+        server
+    }
+
+    fn run(&self, receiver: Receiver) {
+        let mut ports = vec![self.port];
+        if let Some(mouth_port) = self.mouth_port.filter(|p| *p != self.port) {
+            ports.push(mouth_port);
+        }
+
+        for port in ports {
+            let socket = UdpSocket::bind(("0.0.0.0", port)).unwrap();
+            let receiver = receiver.clone();
+
+            std::thread::spawn(move || {
+                println!("OSC socket loop on port {port}");
+                loop {
+                    // Receives a single datagram message on the socket. If `buf` is too small to hold
+                    // the message, it will be cut off.
+                    let mut buf = [0; rosc::decoder::MTU];
+                    match socket.recv_from(&mut buf) {
+                        Ok((size, _addr)) => {
+                            // println!("Received packet with size {} from: {}", size, addr);
+                            receiver.handle_datagram(&buf[..size]);
+                        }
+                        Err(e) => {
+                            println!("Error receiving from socket: {}", e);
+                            break;
+                        }
+                    }
+                }
+            });
+        }
+    }
+}
+
+impl StoreSource for OSCServer {
+    fn store(&self) -> &GazeStore {
+        &self.store
+    }
+}
+
+/// Decodes OSC packets into a `GazeStore`, shared by the socket threads.
+#[derive(Clone)]
+pub(super) struct Receiver {
+    store: GazeStore,
+    eye_addresses: EyeAddresses,
+    pupil_units: PupilUnits,
+    mouth_address_prefix: String,
+    control_address_prefix: String,
+}
+
+impl Receiver {
+    pub(super) fn new(config: &Config, store: GazeStore) -> Receiver {
+        Receiver {
+            store,
+            eye_addresses: EyeAddresses::from_config(config),
+            pupil_units: PupilUnits::from_config(config),
+            mouth_address_prefix: config.get_string("osc.mouth_prefix", "/"),
+            control_address_prefix: config.get_string("osc.control_prefix", "/etvr/"),
+        }
+    }
+
+    pub(super) fn handle_datagram(&self, buf: &[u8]) {
+        match rosc::decoder::decode_udp(buf) {
+            Ok((_, packet)) => self.handle_packet(packet, None),
+            Err(e) => println!("Can't decode OSC packet: {:?}", e),
+        }
+    }
+
+    fn handle_packet(&self, packet: OscPacket, sender_time: Option<SystemTime>) {
+        match packet {
+            OscPacket::Message(msg) => self.handle_message(&msg, sender_time),
+            OscPacket::Bundle(bundle) => {
+                // A time tag of 1 means "immediately" and carries no time.
+                let sender_time = if bundle.timetag == OscTime::from((0, 1)) {
+                    sender_time
+                } else {
+                    Some(SystemTime::from(bundle.timetag))
+                };
+
+                for packet in bundle.content {
+                    self.handle_packet(packet, sender_time);
+                }
+            }
+        }
+    }
+
+    fn handle_message(&self, msg: &OscMessage, sender_time: Option<SystemTime>) {
+        // println!("OSC address: {}", msg.addr);
+        // println!("OSC arguments: {:?}", msg.args);
+        if msg.addr == "/tracking/eye/LeftRightPitchYaw" {
+            let Some(angles) = float_args::<4>(msg) else {
+                return;
+            };
+            let [l_pitch, l_yaw, r_pitch, r_yaw] = angles.map(|a| -a.to_radians());
+
+            self.store
+                .update_gaze(sender_time, Eyes::Both, |eye_gaze_data| {
+                    eye_gaze_data.l_direction = pose::direction_from_angles(l_pitch, l_yaw);
+                    eye_gaze_data.r_direction = pose::direction_from_angles(r_pitch, r_yaw);
+                });
+        }
+        if msg.addr == "/tracking/eye/LeftRightVec" {
+            let Some([l_x, l_y, l_z, r_x, r_y, r_z]) = float_args::<6>(msg) else {
+                return;
+            };
+
+            // The tracker sends +Z forward, OpenXR looks down -Z.
+            let l_direction = pose::normalize_direction([l_x, l_y, -l_z]);
+            let r_direction = pose::normalize_direction([r_x, r_y, -r_z]);
+            self.store
+                .update_gaze(sender_time, Eyes::Both, |eye_gaze_data| {
+                    eye_gaze_data.l_direction = l_direction;
+                    eye_gaze_data.r_direction = r_direction;
+                });
+        }
+
+        if msg.addr == self.eye_addresses.l_pitch_yaw || msg.addr == self.eye_addresses.r_pitch_yaw
+        {
+            self.handle_single_eye_message(msg, sender_time);
+        }
+
+        self.handle_eye_message(msg);
+        self.handle_control_message(msg);
+        self.handle_mouth_message(msg);
+    }
+
+    /// Gaze of a single eye, for trackers that lose one eye at a time. Same units as
+    /// `/tracking/eye/LeftRightPitchYaw`.
+    fn handle_single_eye_message(&self, msg: &OscMessage, sender_time: Option<SystemTime>) {
+        let Some([pitch, yaw]) = float_args::<2>(msg).map(|a| a.map(|v| -v.to_radians())) else {
+            return;
+        };
+
+        let direction = pose::direction_from_angles(pitch, yaw);
+        if msg.addr == self.eye_addresses.l_pitch_yaw {
+            self.store
+                .update_gaze(sender_time, Eyes::Left, |d| d.l_direction = direction);
+        } else {
+            self.store
+                .update_gaze(sender_time, Eyes::Right, |d| d.r_direction = direction);
+        }
+    }
+
+    fn handle_eye_message(&self, msg: &OscMessage) {
+        let addresses = &self.eye_addresses;
+
+        // Degrees, counter-clockwise from the user's point of view.
+        if msg.addr == addresses.l_torsion || msg.addr == addresses.r_torsion {
+            let Some(value) = float_arg(msg) else {
+                return;
+            };
+            let torsion = Some(value.to_radians());
+            if msg.addr == addresses.l_torsion {
+                self.store.update_eyes(|d| d.l_torsion = torsion);
+            } else {
+                self.store.update_eyes(|d| d.r_torsion = torsion);
+            }
+            return;
+        }
+
+        if msg.addr == addresses.l_pupil_diameter || msg.addr == addresses.r_pupil_diameter {
+            let Some(value) = float_arg(msg) else {
+                return;
+            };
+            let diameter = Some(self.pupil_units.to_millimetres(value));
+            let time = SystemTime::now();
+            if msg.addr == addresses.l_pupil_diameter {
+                self.store.update_eyes(|d| {
+                    d.l_pupil_diameter = diameter;
+                    d.l_pupil_time = time;
+                });
+            } else {
+                self.store.update_eyes(|d| {
+                    d.r_pupil_diameter = diameter;
+                    d.r_pupil_time = time;
+                });
+            }
+            return;
+        }
+
+        let optional_field: Option<fn(&mut EyeGazeData) -> &mut Option<f32>> =
+            if msg.addr == addresses.l_confidence {
+                Some(|d| &mut d.l_confidence)
+            } else if msg.addr == addresses.r_confidence {
+                Some(|d| &mut d.r_confidence)
+            } else {
+                None
+            };
+
+        if let Some(field) = optional_field {
+            let Some(value) = float_arg(msg) else {
+                return;
+            };
+
+            self.store
+                .update_eyes(|d| *field(d) = Some(value.clamp(0.0, 1.0)));
+            return;
+        }
+
+        let field: fn(&mut EyeGazeData) -> &mut f32 = if msg.addr == addresses.l_openness {
+            |d| &mut d.l_openness
+        } else if msg.addr == addresses.r_openness {
+            |d| &mut d.r_openness
+        } else if msg.addr == addresses.l_widen {
+            |d| &mut d.l_widen
+        } else if msg.addr == addresses.r_widen {
+            |d| &mut d.r_widen
+        } else if msg.addr == addresses.l_squeeze {
+            |d| &mut d.l_squeeze
+        } else if msg.addr == addresses.r_squeeze {
+            |d| &mut d.r_squeeze
+        } else {
+            return;
+        };
+
+        let Some(value) = float_arg(msg) else {
+            return;
+        };
+
+        self.store
+            .update_eyes(|d| *field(d) = value.clamp(0.0, 1.0));
+    }
+
+    fn handle_mouth_message(&self, msg: &OscMessage) {
+        let Some(name) = msg.addr.strip_prefix(&self.mouth_address_prefix) else {
+            return;
+        };
+        let Some(shape) = mouth::NAMES.iter().position(|n| *n == name) else {
+            return;
+        };
+
+        let Some(value) = float_arg(msg) else {
+            return;
+        };
+
+        self.store
+            .update_mouth(|d| d.weights[shape] = value.clamp(0.0, 1.0));
+    }
+
+    fn handle_control_message(&self, msg: &OscMessage) {
+        let Some(name) = msg.addr.strip_prefix(&self.control_address_prefix) else {
+            return;
+        };
+
+        let string_arg = || msg.args.first().and_then(|a| a.clone().string());
+        let command = match name {
+            "calibration/start" => Command::StartCalibration {
+                model: string_arg(),
+            },
+            "calibration/target" => {
+                let Some([pitch, yaw]) = float_args::<2>(msg) else {
+                    return;
+                };
+                Command::CalibrationTarget {
+                    pitch: pitch.to_radians(),
+                    yaw: yaw.to_radians(),
+                }
+            }
+            "calibration/finish" | "calibration/load" => {
+                let Some(profile) = string_arg() else {
+                    println!("Expected a profile name for {}", msg.addr);
+                    return;
+                };
+                if name == "calibration/finish" {
+                    Command::FinishCalibration { profile }
+                } else {
+                    Command::LoadCalibration { profile }
+                }
+            }
+            "calibration/clear" => Command::ClearCalibration,
+            "recenter" => Command::Recenter,
+            _ => {
+                println!("Unknown control message {}", msg.addr);
+                return;
+            }
+        };
+
+        self.store.push_command(command);
+    }
+}
+
+/// Exactly `N` float arguments, anything else is ignored with a message.
+fn float_args<const N: usize>(msg: &OscMessage) -> Option<[f32; N]> {
+    let values = msg
+        .args
+        .iter()
+        .map(|a| a.clone().float())
+        .collect::<Option<Vec<f32>>>()
+        .and_then(|values| values.try_into().ok());
+    if values.is_none() {
+        println!("Expected {N} float arguments for {}", msg.addr);
+    }
+    values
+}
+
+fn float_arg(msg: &OscMessage) -> Option<f32> {
+    let value = msg.args.first().and_then(|a| a.clone().float());
+    if value.is_none() {
+        println!("Expected a float argument for {}", msg.addr);
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use rosc::OscType;
+
+    use super::*;
+
+    fn receiver() -> Receiver {
+        let config = Config::parse("");
+        Receiver::new(&config, GazeStore::new(&config))
+    }
+
+    fn message(addr: &str, args: &[f32]) -> OscMessage {
+        OscMessage {
+            addr: addr.to_string(),
+            args: args.iter().map(|&v| OscType::Float(v)).collect(),
+        }
+    }
+
+    fn assert_direction_eq(actual: [f32; 3], expected: [f32; 3]) {
+        let close = actual
+            .iter()
+            .zip(expected)
+            .all(|(a, e)| (a - e).abs() < 1e-5);
+        assert!(close, "{actual:?} != {expected:?}");
+    }
+
+    #[test]
+    fn left_right_pitch_yaw_is_parsed() {
+        let receiver = receiver();
+        receiver.handle_message(
+            &message("/tracking/eye/LeftRightPitchYaw", &[10.0, -20.0, 0.0, 30.0]),
+            None,
+        );
+
+        // The tracker sends pitch down and yaw right as positive degrees.
+        let d = receiver.store.eye_gaze_data();
+        let l = pose::direction_from_angles(-10f32.to_radians(), 20f32.to_radians());
+        let r = pose::direction_from_angles(0.0, -30f32.to_radians());
+        assert_direction_eq(d.l_direction, l);
+        assert_direction_eq(d.r_direction, r);
+        assert!(d.received.is_some());
+    }
+
+    #[test]
+    fn left_right_vec_is_parsed() {
+        let receiver = receiver();
+        receiver.handle_message(
+            &message(
+                "/tracking/eye/LeftRightVec",
+                &[0.0, 0.0, 2.0, 1.0, 0.0, 1.0],
+            ),
+            None,
+        );
+
+        let d = receiver.store.eye_gaze_data();
+        assert_direction_eq(d.l_direction, [0.0, 0.0, -1.0]);
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert_direction_eq(d.r_direction, [half, 0.0, -half]);
+    }
+
+    #[test]
+    fn malformed_gaze_is_ignored() {
+        let receiver = receiver();
+        let mut wrong_type = message("/tracking/eye/LeftRightPitchYaw", &[1.0, 2.0, 3.0]);
+        wrong_type.args.push(OscType::Int(4));
+        let malformed = [
+            message("/tracking/eye/LeftRightPitchYaw", &[]),
+            message("/tracking/eye/LeftRightPitchYaw", &[1.0, 2.0, 3.0]),
+            message(
+                "/tracking/eye/LeftRightPitchYaw",
+                &[1.0, 2.0, 3.0, 4.0, 5.0],
+            ),
+            wrong_type,
+            message("/tracking/eye/LeftRightVec", &[0.0, 0.0, 1.0]),
+            message("/tracking/eye/LeftRightVec", &[0.0, 0.0, 1.0, 0.0, 0.0]),
+            message("/tracking/eye/LeftPitchYaw", &[1.0]),
+        ];
+        for msg in &malformed {
+            receiver.handle_message(msg, None);
+        }
+
+        let d = receiver.store.eye_gaze_data();
+        assert!(d.received.is_none());
+        assert!(receiver.store.eye_gaze_history().is_empty());
+    }
+}
@@ -0,0 +1,262 @@
+//! Compact binary recordings of gaze samples.
+//!
+//! A recording is `MAGIC` followed by fixed size little-endian records: the receive, sender, left
+//! eye, right eye, left pupil and right pupil times in nanoseconds since the Unix epoch, 0 if
+//! unset, then for the left and then the right eye the gaze direction x, y and z, torsion,
+//! openness, widen, squeeze, confidence and pupil diameter as `f32`. Missing optional values are
+//! stored as NaN.
+
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    sync::mpsc::{self, Sender},
+    time::{Duration, SystemTime},
+};
+
+use super::EyeGazeData;
+
+const MAGIC: &[u8; 8] = b"ETVRGAZ2";
+const TIME_COUNT: usize = 6;
+const EYE_VALUE_COUNT: usize = 9;
+const RECORD_SIZE: usize = TIME_COUNT * 8 + 2 * EYE_VALUE_COUNT * 4;
+
+/// Start writing samples sent to the returned channel to `path` from a background thread.
+pub fn start_recording(path: &Path) -> io::Result<Sender<EyeGazeData>> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(MAGIC)?;
+
+    let (sender, receiver) = mpsc::channel::<EyeGazeData>();
+    let path = path.to_path_buf();
+    std::thread::spawn(move || {
+        println!("Recording gaze to {}", path.display());
+        for sample in receiver {
+            // Flush every sample, the app can be killed at any moment.
+            let result = writer
+                .write_all(&encode(&sample))
+                .and_then(|_| writer.flush());
+            if let Err(e) = result {
+                println!("Error writing gaze recording: {}", e);
+                break;
+            }
+        }
+    });
+
+    Ok(sender)
+}
+
+pub fn read_recording(path: &Path) -> io::Result<Vec<EyeGazeData>> {
+    read_samples(BufReader::new(File::open(path)?))
+}
+
+fn read_samples(mut reader: impl Read) -> io::Result<Vec<EyeGazeData>> {
+    let mut magic = [0; MAGIC.len()];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a gaze recording",
+        ));
+    }
+
+    let mut samples = Vec::new();
+    let mut record = [0; RECORD_SIZE];
+    loop {
+        match reader.read_exact(&mut record) {
+            Ok(()) => samples.push(decode(&record)),
+            // A truncated last record is expected if the app was killed while recording.
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(samples)
+}
+
+fn encode(sample: &EyeGazeData) -> [u8; RECORD_SIZE] {
+    let nanos = |t: Option<SystemTime>| {
+        t.and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_nanos() as u64)
+    };
+    let times = [
+        Some(sample.time),
+        sample.sender_time,
+        Some(sample.l_time),
+        Some(sample.r_time),
+        Some(sample.l_pupil_time),
+        Some(sample.r_pupil_time),
+    ]
+    .map(nanos);
+
+    let optional = |v: Option<f32>| v.unwrap_or(f32::NAN);
+    let [l_x, l_y, l_z] = sample.l_direction;
+    let [r_x, r_y, r_z] = sample.r_direction;
+    let values: [f32; 2 * EYE_VALUE_COUNT] = [
+        l_x,
+        l_y,
+        l_z,
+        optional(sample.l_torsion),
+        sample.l_openness,
+        sample.l_widen,
+        sample.l_squeeze,
+        optional(sample.l_confidence),
+        optional(sample.l_pupil_diameter),
+        r_x,
+        r_y,
+        r_z,
+        optional(sample.r_torsion),
+        sample.r_openness,
+        sample.r_widen,
+        sample.r_squeeze,
+        optional(sample.r_confidence),
+        optional(sample.r_pupil_diameter),
+    ];
+
+    let bytes = times
+        .iter()
+        .flat_map(|t| t.to_le_bytes())
+        .chain(values.iter().flat_map(|v| v.to_le_bytes()));
+    let mut record = [0; RECORD_SIZE];
+    for (dst, src) in record.iter_mut().zip(bytes) {
+        *dst = src;
+    }
+    record
+}
+
+fn decode(record: &[u8; RECORD_SIZE]) -> EyeGazeData {
+    let time = |i: usize| {
+        let start = i * 8;
+        let nanos = u64::from_le_bytes(record[start..start + 8].try_into().unwrap());
+        (nanos != 0).then(|| SystemTime::UNIX_EPOCH + Duration::from_nanos(nanos))
+    };
+    let value = |eye: usize, i: usize| {
+        let start = TIME_COUNT * 8 + (eye * EYE_VALUE_COUNT + i) * 4;
+        f32::from_le_bytes(record[start..start + 4].try_into().unwrap())
+    };
+    let optional = |eye: usize, i: usize| Some(value(eye, i)).filter(|v| !v.is_nan());
+    let direction = |eye: usize| [value(eye, 0), value(eye, 1), value(eye, 2)];
+    let epoch_if_unset = |t: Option<SystemTime>| t.unwrap_or(SystemTime::UNIX_EPOCH);
+
+    EyeGazeData {
+        l_direction: direction(0),
+        l_torsion: optional(0, 3),
+        l_openness: value(0, 4),
+        l_widen: value(0, 5),
+        l_squeeze: value(0, 6),
+        l_confidence: optional(0, 7),
+        l_pupil_diameter: optional(0, 8),
+        r_direction: direction(1),
+        r_torsion: optional(1, 3),
+        r_openness: value(1, 4),
+        r_widen: value(1, 5),
+        r_squeeze: value(1, 6),
+        r_confidence: optional(1, 7),
+        r_pupil_diameter: optional(1, 8),
+        time: epoch_if_unset(time(0)),
+        sender_time: time(1),
+        l_time: epoch_if_unset(time(2)),
+        r_time: epoch_if_unset(time(3)),
+        l_pupil_time: epoch_if_unset(time(4)),
+        r_pupil_time: epoch_if_unset(time(5)),
+        received: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at_nanos(nanos: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_nanos(nanos)
+    }
+
+    fn sample(nanos: u64) -> EyeGazeData {
+        EyeGazeData {
+            l_direction: [-0.1, 0.2, -0.97],
+            r_direction: [0.05, -0.3, -0.95],
+            l_torsion: Some(0.04),
+            r_torsion: None,
+            l_openness: 0.9,
+            r_openness: 0.1,
+            l_widen: 0.2,
+            r_widen: 0.0,
+            l_squeeze: 0.0,
+            r_squeeze: 0.6,
+            l_confidence: None,
+            r_confidence: Some(0.75),
+            l_pupil_diameter: Some(3.5),
+            r_pupil_diameter: None,
+            l_time: at_nanos(nanos - 1_000),
+            r_time: at_nanos(nanos),
+            l_pupil_time: at_nanos(nanos - 5_000_000),
+            r_pupil_time: SystemTime::UNIX_EPOCH,
+            time: at_nanos(nanos),
+            sender_time: Some(at_nanos(nanos - 123_456)),
+            received: None,
+        }
+    }
+
+    fn recording(samples: &[EyeGazeData]) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        for sample in samples {
+            bytes.extend_from_slice(&encode(sample));
+        }
+        bytes
+    }
+
+    #[test]
+    fn samples_round_trip() {
+        let samples = [
+            sample(1_700_000_000_123_456_789),
+            sample(1_700_000_000_131_789_012),
+        ];
+        let read = read_samples(&recording(&samples)[..]).unwrap();
+        assert_eq!(read, samples);
+    }
+
+    #[test]
+    fn unset_optional_values_round_trip() {
+        let sample = EyeGazeData {
+            sender_time: None,
+            l_torsion: None,
+            r_torsion: None,
+            l_confidence: None,
+            r_confidence: None,
+            l_pupil_diameter: None,
+            r_pupil_diameter: None,
+            ..sample(1_700_000_000_000_000_000)
+        };
+        assert_eq!(decode(&encode(&sample)), sample);
+    }
+
+    #[test]
+    fn missing_values_are_nan() {
+        let record = encode(&sample(1_700_000_000_000_000_000));
+        let value = |eye: usize, i: usize| {
+            let start = TIME_COUNT * 8 + (eye * EYE_VALUE_COUNT + i) * 4;
+            f32::from_le_bytes(record[start..start + 4].try_into().unwrap())
+        };
+        // Right torsion, left confidence and right pupil diameter.
+        assert!(value(1, 3).is_nan());
+        assert!(value(0, 7).is_nan());
+        assert!(value(1, 8).is_nan());
+        assert_eq!(value(0, 3), 0.04);
+    }
+
+    #[test]
+    fn truncated_last_record_is_dropped() {
+        let samples = [
+            sample(1_700_000_000_000_000_000),
+            sample(1_700_000_000_008_000_000),
+        ];
+        let mut bytes = recording(&samples);
+        bytes.extend_from_slice(&encode(&sample(1_700_000_000_016_000_000))[..RECORD_SIZE / 2]);
+        assert_eq!(read_samples(&bytes[..]).unwrap(), samples);
+    }
+
+    #[test]
+    fn other_files_are_rejected() {
+        let error = read_samples(&b"ETVRGAZ1 and more"[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
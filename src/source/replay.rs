@@ -0,0 +1,146 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
+
+use super::{recording, EyeGazeData, Eyes, GazeStore, StoreSource};
+use crate::config::Config;
+
+/// Shortest time one pass of a looped replay takes, so that a recording whose samples all have the
+/// same time doesn't keep a core busy.
+const MIN_LOOP_PERIOD: Duration = Duration::from_nanos(1_000_000_000 / 120);
+
+/// Plays back a gaze recording in real time, as if the samples were arriving from the tracker.
+pub struct ReplaySource {
+    store: GazeStore,
+}
+
+impl ReplaySource {
+    pub fn start(config: &Config) -> ReplaySource {
+        let source = ReplaySource {
+            store: GazeStore::new(config),
+        };
+
+        let Some(path) = config.get_str("replay.path").map(PathBuf::from) else {
+            println!("No replay.path set, nothing to replay");
+            return source;
+        };
+        let is_looping: bool = config.get("replay.loop", true);
+
+        let samples = match recording::read_recording(&path) {
+            Ok(samples) if !samples.is_empty() => samples,
+            Ok(_) => {
+                println!("Gaze recording {} is empty", path.display());
+                return source;
+            }
+            Err(e) => {
+                println!("Can't read gaze recording {}: {}", path.display(), e);
+                return source;
+            }
+        };
+
+        let store = source.store.clone();
+        std::thread::spawn(move || {
+            println!(
+                "Replaying {} gaze samples from {}",
+                samples.len(),
+                path.display()
+            );
+            let first_time = samples[0].time;
+            let loop_period = loop_period(&samples);
+            loop {
+                let start = Instant::now();
+                let start_time = SystemTime::now();
+                for sample in &samples {
+                    let offset = sample.time.duration_since(first_time).unwrap_or_default();
+                    if let Some(wait) = offset.checked_sub(start.elapsed()) {
+                        std::thread::sleep(wait);
+                    }
+
+                    // Shift the sender and pupil times by as much as the receive time moved.
+                    let shift = |t: SystemTime| match t.duration_since(first_time) {
+                        Ok(d) => start_time + d,
+                        Err(e) => start_time - e.duration(),
+                    };
+                    let shift_if_set = |t: SystemTime| {
+                        if t == SystemTime::UNIX_EPOCH {
+                            t
+                        } else {
+                            shift(t)
+                        }
+                    };
+                    let sender_time = sample.sender_time.map(shift);
+                    let eyes = match (sample.l_time == sample.time, sample.r_time == sample.time) {
+                        (true, false) => Eyes::Left,
+                        (false, true) => Eyes::Right,
+                        _ => Eyes::Both,
+                    };
+                    store.update_gaze(sender_time, eyes, |d| {
+                        *d = EyeGazeData {
+                            l_time: d.l_time,
+                            r_time: d.r_time,
+                            time: d.time,
+                            sender_time: d.sender_time,
+                            l_pupil_time: shift_if_set(sample.l_pupil_time),
+                            r_pupil_time: shift_if_set(sample.r_pupil_time),
+                            ..*sample
+                        }
+                    });
+                }
+
+                if !is_looping {
+                    break;
+                }
+                if let Some(wait) = loop_period.checked_sub(start.elapsed()) {
+                    std::thread::sleep(wait);
+                }
+            }
+        });
+
+        source
+    }
+}
+
+/// How long one pass of a looped replay takes: the recording plus a mean sample interval before
+/// the first sample comes again, at least `MIN_LOOP_PERIOD`.
+fn loop_period(samples: &[EyeGazeData]) -> Duration {
+    let (Some(first), Some(last)) = (samples.first(), samples.last()) else {
+        return MIN_LOOP_PERIOD;
+    };
+    let duration = last.time.duration_since(first.time).unwrap_or_default();
+    let intervals = (samples.len() as u32 - 1).max(1);
+    (duration + duration / intervals).max(MIN_LOOP_PERIOD)
+}
+
+impl StoreSource for ReplaySource {
+    fn store(&self) -> &GazeStore {
+        &self.store
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at_millis(millis: &[u64]) -> Vec<EyeGazeData> {
+        millis
+            .iter()
+            .map(|&m| EyeGazeData {
+                time: SystemTime::UNIX_EPOCH + Duration::from_millis(m),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn loop_period_adds_a_sample_interval() {
+        let period = loop_period(&at_millis(&[1_000, 1_010, 1_020, 1_030]));
+        assert_eq!(period, Duration::from_millis(40));
+    }
+
+    #[test]
+    fn zero_duration_recordings_loop_at_the_minimum_period() {
+        assert_eq!(loop_period(&at_millis(&[1_000, 1_000])), MIN_LOOP_PERIOD);
+        assert_eq!(loop_period(&at_millis(&[1_000])), MIN_LOOP_PERIOD);
+    }
+}
@@ -0,0 +1,64 @@
+use std::time::{Duration, Instant};
+
+use super::{Eyes, GazeStore, StoreSource};
+use crate::{config::Config, pose};
+
+/// How long a synthetic blink keeps the eyes closed.
+const BLINK_DURATION: Duration = Duration::from_millis(150);
+
+/// Generates gaze without a tracker, for testing apps and the layer itself.
+///
+/// Both eyes follow a slow Lissajous figure and blink at a fixed interval.
+pub struct SyntheticSource {
+    store: GazeStore,
+}
+
+impl SyntheticSource {
+    pub fn start(config: &Config) -> SyntheticSource {
+        let source = SyntheticSource {
+            store: GazeStore::new(config),
+        };
+
+        let rate: f32 = config.get("synthetic.rate", 120.0);
+        let amplitude = config.get::<f32>("synthetic.amplitude", 15.0).to_radians();
+        let period: f32 = config.get("synthetic.period", 4.0);
+        let blink_interval: f32 = config.get("synthetic.blink_interval", 5.0);
+        let interval = Duration::from_secs_f32(1.0 / rate.max(1.0));
+
+        let store = source.store.clone();
+        std::thread::spawn(move || {
+            println!("Synthetic gaze at {rate} Hz");
+            let start = Instant::now();
+            loop {
+                let t = start.elapsed().as_secs_f32();
+                let phase = std::f32::consts::TAU * t / period;
+                let pitch = amplitude * (2.0 * phase).sin();
+                let yaw = amplitude * phase.sin();
+                let direction = pose::direction_from_angles(pitch, yaw);
+
+                let is_blinking =
+                    blink_interval > 0.0 && t % blink_interval < BLINK_DURATION.as_secs_f32();
+                let openness = if is_blinking { 0.0 } else { 1.0 };
+
+                store.update_gaze(None, Eyes::Both, |d| {
+                    d.l_direction = direction;
+                    d.r_direction = direction;
+                    d.l_openness = openness;
+                    d.r_openness = openness;
+                    d.l_confidence = Some(1.0);
+                    d.r_confidence = Some(1.0);
+                });
+
+                std::thread::sleep(interval);
+            }
+        });
+
+        source
+    }
+}
+
+impl StoreSource for SyntheticSource {
+    fn store(&self) -> &GazeStore {
+        &self.store
+    }
+}
@@ -0,0 +1,178 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    config::Config,
+    source::{EyeGazeData, MouthData},
+};
+
+/// Gaze samples used to measure the sample rate.
+const RATE_WINDOW: usize = 32;
+
+/// Decides when eye and mouth data is too old to be reported as tracked.
+///
+/// Ages are measured on the monotonic clock from when the data was received. The eye timeout is
+/// `staleness.timeout`, or with `staleness.adaptive` a multiple of the measured interval between
+/// gaze samples, so a fast tracker is noticed missing sooner and a slow one isn't reported as
+/// lost between samples.
+#[derive(Debug, Clone, Copy)]
+pub struct Staleness {
+    timeout: Duration,
+    /// Multiple of the sample interval used as the eye timeout, if adaptive.
+    adaptive_factor: Option<f32>,
+    min_timeout: Duration,
+    max_timeout: Duration,
+    /// Current eye timeout.
+    eye_timeout: Duration,
+}
+
+impl Staleness {
+    pub fn new(config: &Config) -> Staleness {
+        let timeout = Duration::from_secs_f32(config.get("staleness.timeout", 0.05));
+
+        Staleness {
+            timeout,
+            adaptive_factor: config
+                .get("staleness.adaptive", false)
+                .then(|| config.get("staleness.adaptive_factor", 4.0)),
+            min_timeout: Duration::from_secs_f32(config.get("staleness.min_timeout", 0.01)),
+            max_timeout: Duration::from_secs_f32(config.get("staleness.max_timeout", 0.25)),
+            eye_timeout: timeout,
+        }
+    }
+
+    /// Adapt the eye timeout to the rate of the gaze samples in `history`, oldest first.
+    pub fn update(&mut self, history: &[EyeGazeData]) {
+        let Some(factor) = self.adaptive_factor else {
+            return;
+        };
+
+        let received: Vec<Instant> = history
+            .iter()
+            .rev()
+            .take(RATE_WINDOW)
+            .filter_map(|s| s.received)
+            .collect();
+        // Newest first.
+        let [last, .., first] = received[..] else {
+            return;
+        };
+
+        let interval = (last - first) / (received.len() - 1) as u32;
+        self.eye_timeout = interval
+            .mul_f32(factor)
+            .max(self.min_timeout)
+            .min(self.max_timeout);
+    }
+
+    pub fn is_eye_fresh(&self, sample: &EyeGazeData) -> bool {
+        is_fresh(sample.received, self.eye_timeout)
+    }
+
+    pub fn is_mouth_fresh(&self, mouth_data: &MouthData) -> bool {
+        is_fresh(mouth_data.received, self.timeout)
+    }
+}
+
+fn is_fresh(received: Option<Instant>, timeout: Duration) -> bool {
+    received.is_some_and(|t| t.elapsed() < timeout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adaptive() -> Staleness {
+        Staleness::new(&Config::parse(
+            "[staleness]\nadaptive = true\nadaptive_factor = 4\nmin_timeout = 0.01\nmax_timeout = 0.25",
+        ))
+    }
+
+    fn assert_timeout(staleness: &Staleness, millis: u64) {
+        let expected = Duration::from_millis(millis);
+        let error = staleness.eye_timeout.abs_diff(expected);
+        assert!(
+            error < Duration::from_micros(1),
+            "{:?} isn't {:?}",
+            staleness.eye_timeout,
+            expected
+        );
+    }
+
+    /// Samples received `intervals_ms` apart, the last one `age` ago.
+    fn history(intervals_ms: &[u64], age: Duration) -> Vec<EyeGazeData> {
+        let total: u64 = intervals_ms.iter().sum();
+        let first = Instant::now() - age - Duration::from_millis(total);
+        let mut received = first;
+        let mut samples = vec![EyeGazeData {
+            received: Some(received),
+            ..Default::default()
+        }];
+        for &ms in intervals_ms {
+            received += Duration::from_millis(ms);
+            samples.push(EyeGazeData {
+                received: Some(received),
+                ..Default::default()
+            });
+        }
+        samples
+    }
+
+    #[test]
+    fn fixed_timeout_ignores_the_rate() {
+        let mut staleness = Staleness::new(&Config::parse(""));
+        staleness.update(&history(&[100; 8], Duration::ZERO));
+        assert_timeout(&staleness, 50);
+    }
+
+    #[test]
+    fn regular_stream_scales_the_interval() {
+        let mut staleness = adaptive();
+        staleness.update(&history(&[10; 8], Duration::ZERO));
+        assert_timeout(&staleness, 40);
+    }
+
+    #[test]
+    fn jittery_stream_uses_the_mean_interval() {
+        let mut staleness = adaptive();
+        staleness.update(&history(&[5, 15, 2, 18, 10, 10, 7, 13], Duration::ZERO));
+        assert_timeout(&staleness, 40);
+    }
+
+    #[test]
+    fn only_recent_samples_set_the_rate() {
+        let mut staleness = adaptive();
+        let mut intervals = vec![50; 16];
+        intervals.extend([10; RATE_WINDOW - 1]);
+        staleness.update(&history(&intervals, Duration::ZERO));
+        assert_timeout(&staleness, 40);
+    }
+
+    #[test]
+    fn timeout_is_clamped() {
+        let mut staleness = adaptive();
+        staleness.update(&history(&[1; 8], Duration::ZERO));
+        assert_timeout(&staleness, 10);
+        staleness.update(&history(&[200; 8], Duration::ZERO));
+        assert_timeout(&staleness, 250);
+    }
+
+    #[test]
+    fn stopped_stream_goes_stale() {
+        let mut staleness = adaptive();
+        let stopped = history(&[10; 8], Duration::from_millis(500));
+        staleness.update(&stopped);
+        assert!(!staleness.is_eye_fresh(stopped.last().unwrap()));
+
+        let running = history(&[10; 8], Duration::ZERO);
+        staleness.update(&running);
+        assert!(staleness.is_eye_fresh(running.last().unwrap()));
+    }
+
+    #[test]
+    fn too_few_samples_keep_the_timeout() {
+        let mut staleness = adaptive();
+        staleness.update(&history(&[], Duration::ZERO));
+        staleness.update(&[EyeGazeData::default()]);
+        assert_timeout(&staleness, 50);
+    }
+}
@@ -0,0 +1,183 @@
+//! Definitions for extensions that are missing from `openxr-sys`.
+
+use std::ffi::c_void;
+
+use openxr_sys::{Bool32, Result, Session, StructureType, Time};
+
+// XR_FB_face_tracking2
+
+// `StructureType::from_raw` isn't `const`, so structure types are kept as raw values.
+pub const TYPE_SYSTEM_FACE_TRACKING_PROPERTIES2_FB: i32 = 1000287013;
+pub const TYPE_FACE_TRACKER_CREATE_INFO2_FB: i32 = 1000287014;
+pub const TYPE_FACE_EXPRESSION_INFO2_FB: i32 = 1000287015;
+pub const TYPE_FACE_EXPRESSION_WEIGHTS2_FB: i32 = 1000287016;
+
+#[repr(transparent)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct FaceTracker2FB(u64);
+
+impl FaceTracker2FB {
+    pub fn from_raw(x: u64) -> Self {
+        Self(x)
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct SystemFaceTrackingProperties2FB {
+    pub ty: StructureType,
+    pub next: *mut c_void,
+    pub supports_visual_face_tracking: Bool32,
+    pub supports_audio_face_tracking: Bool32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct FaceTrackerCreateInfo2FB {
+    pub ty: StructureType,
+    pub next: *const c_void,
+    pub face_expression_set: i32,
+    pub requested_data_source_count: u32,
+    pub requested_data_sources: *mut i32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct FaceExpressionInfo2FB {
+    pub ty: StructureType,
+    pub next: *const c_void,
+    pub time: Time,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct FaceExpressionWeights2FB {
+    pub ty: StructureType,
+    pub next: *mut c_void,
+    pub weight_count: u32,
+    pub weights: *mut f32,
+    pub confidence_count: u32,
+    pub confidences: *mut f32,
+    pub is_valid: Bool32,
+    pub is_eye_following_blendshapes_valid: Bool32,
+    pub data_source: i32,
+    pub time: Time,
+}
+
+pub const FACE_EXPRESSION_SET2_DEFAULT_FB: i32 = 0;
+pub const FACE_TRACKING_DATA_SOURCE2_VISUAL_FB: i32 = 0;
+
+pub const FACE_EXPRESSION2_COUNT_FB: usize = 70;
+pub const FACE_CONFIDENCE2_COUNT_FB: usize = 2;
+
+pub const FACE_CONFIDENCE2_LOWER_FACE_FB: usize = 0;
+pub const FACE_CONFIDENCE2_UPPER_FACE_FB: usize = 1;
+
+pub type CreateFaceTracker2FB = unsafe extern "system" fn(
+    session: Session,
+    create_info: *const FaceTrackerCreateInfo2FB,
+    face_tracker: *mut FaceTracker2FB,
+) -> Result;
+pub type DestroyFaceTracker2FB = unsafe extern "system" fn(face_tracker: FaceTracker2FB) -> Result;
+pub type GetFaceExpressionWeights2FB = unsafe extern "system" fn(
+    face_tracker: FaceTracker2FB,
+    expression_info: *const FaceExpressionInfo2FB,
+    expression_weights: *mut FaceExpressionWeights2FB,
+) -> Result;
+
+/// Indices into `XrFaceExpressionWeights2FB::weights`.
+#[allow(dead_code)]
+pub mod face_expression2 {
+    pub const BROW_LOWERER_L: usize = 0;
+    pub const BROW_LOWERER_R: usize = 1;
+    pub const CHEEK_PUFF_L: usize = 2;
+    pub const CHEEK_PUFF_R: usize = 3;
+    pub const CHEEK_RAISER_L: usize = 4;
+    pub const CHEEK_RAISER_R: usize = 5;
+    pub const CHEEK_SUCK_L: usize = 6;
+    pub const CHEEK_SUCK_R: usize = 7;
+    pub const CHIN_RAISER_B: usize = 8;
+    pub const CHIN_RAISER_T: usize = 9;
+    pub const DIMPLER_L: usize = 10;
+    pub const DIMPLER_R: usize = 11;
+    pub const EYES_CLOSED_L: usize = 12;
+    pub const EYES_CLOSED_R: usize = 13;
+    pub const EYES_LOOK_DOWN_L: usize = 14;
+    pub const EYES_LOOK_DOWN_R: usize = 15;
+    pub const EYES_LOOK_LEFT_L: usize = 16;
+    pub const EYES_LOOK_LEFT_R: usize = 17;
+    pub const EYES_LOOK_RIGHT_L: usize = 18;
+    pub const EYES_LOOK_RIGHT_R: usize = 19;
+    pub const EYES_LOOK_UP_L: usize = 20;
+    pub const EYES_LOOK_UP_R: usize = 21;
+    pub const INNER_BROW_RAISER_L: usize = 22;
+    pub const INNER_BROW_RAISER_R: usize = 23;
+    pub const JAW_DROP: usize = 24;
+    pub const JAW_SIDEWAYS_LEFT: usize = 25;
+    pub const JAW_SIDEWAYS_RIGHT: usize = 26;
+    pub const JAW_THRUST: usize = 27;
+    pub const LID_TIGHTENER_L: usize = 28;
+    pub const LID_TIGHTENER_R: usize = 29;
+    pub const LIP_CORNER_DEPRESSOR_L: usize = 30;
+    pub const LIP_CORNER_DEPRESSOR_R: usize = 31;
+    pub const LIP_CORNER_PULLER_L: usize = 32;
+    pub const LIP_CORNER_PULLER_R: usize = 33;
+    pub const LIP_FUNNELER_LB: usize = 34;
+    pub const LIP_FUNNELER_LT: usize = 35;
+    pub const LIP_FUNNELER_RB: usize = 36;
+    pub const LIP_FUNNELER_RT: usize = 37;
+    pub const LIP_PRESSOR_L: usize = 38;
+    pub const LIP_PRESSOR_R: usize = 39;
+    pub const LIP_PUCKER_L: usize = 40;
+    pub const LIP_PUCKER_R: usize = 41;
+    pub const LIP_STRETCHER_L: usize = 42;
+    pub const LIP_STRETCHER_R: usize = 43;
+    pub const LIP_SUCK_LB: usize = 44;
+    pub const LIP_SUCK_LT: usize = 45;
+    pub const LIP_SUCK_RB: usize = 46;
+    pub const LIP_SUCK_RT: usize = 47;
+    pub const LIP_TIGHTENER_L: usize = 48;
+    pub const LIP_TIGHTENER_R: usize = 49;
+    pub const LIPS_TOWARD: usize = 50;
+    pub const LOWER_LIP_DEPRESSOR_L: usize = 51;
+    pub const LOWER_LIP_DEPRESSOR_R: usize = 52;
+    pub const MOUTH_LEFT: usize = 53;
+    pub const MOUTH_RIGHT: usize = 54;
+    pub const NOSE_WRINKLER_L: usize = 55;
+    pub const NOSE_WRINKLER_R: usize = 56;
+    pub const OUTER_BROW_RAISER_L: usize = 57;
+    pub const OUTER_BROW_RAISER_R: usize = 58;
+    pub const UPPER_LID_RAISER_L: usize = 59;
+    pub const UPPER_LID_RAISER_R: usize = 60;
+    pub const UPPER_LIP_RAISER_L: usize = 61;
+    pub const UPPER_LIP_RAISER_R: usize = 62;
+    pub const TONGUE_TIP_INTERDENTAL: usize = 63;
+    pub const TONGUE_TIP_ALVEOLAR: usize = 64;
+    pub const TONGUE_FRONT_DORSAL_PALATE: usize = 65;
+    pub const TONGUE_MID_DORSAL_PALATE: usize = 66;
+    pub const TONGUE_BACK_DORSAL_VELAR: usize = 67;
+    pub const TONGUE_OUT: usize = 68;
+    pub const TONGUE_RETREAT: usize = 69;
+}
+
+// XR_EXT_user_presence
+
+pub const TYPE_EVENT_DATA_USER_PRESENCE_CHANGED_EXT: i32 = 1000470000;
+pub const TYPE_SYSTEM_USER_PRESENCE_PROPERTIES_EXT: i32 = 1000470001;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct SystemUserPresencePropertiesEXT {
+    pub ty: StructureType,
+    pub next: *mut c_void,
+    pub supports_user_presence: Bool32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct EventDataUserPresenceChangedEXT {
+    pub ty: StructureType,
+    pub next: *const c_void,
+    pub session: Session,
+    pub is_user_present: Bool32,
+}
@@ -0,0 +1,51 @@
+use std::time::SystemTime;
+
+use openxr_sys::Time;
+
+/// Current `XrTime`.
+///
+/// Runtimes on Android and Linux use `CLOCK_MONOTONIC` nanoseconds for `XrTime`.
+pub fn now() -> Time {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+
+    // The fields are 32-bit on 32-bit targets.
+    #[allow(clippy::unnecessary_cast)]
+    Time::from_nanos(ts.tv_sec as i64 * 1_000_000_000 + ts.tv_nsec as i64)
+}
+
+/// Convert a wall clock time to `XrTime` using the current offset between the clocks.
+pub fn from_system_time(time: SystemTime) -> Time {
+    let now_system = SystemTime::now();
+    let now = now().as_nanos();
+
+    match now_system.duration_since(time) {
+        Ok(age) => Time::from_nanos(now - age.as_nanos() as i64),
+        Err(e) => Time::from_nanos(now + e.duration().as_nanos() as i64),
+    }
+}
+
+/// Like `from_system_time`, but the `UNIX_EPOCH` of data that hasn't arrived yet is 0, which
+/// OpenXR uses for no time.
+pub fn from_sample_time(time: SystemTime) -> Time {
+    if time == SystemTime::UNIX_EPOCH {
+        Time::from_nanos(0)
+    } else {
+        from_system_time(time)
+    }
+}
+
+/// Convert an `XrTime` to wall clock time using the current offset between the clocks.
+pub fn to_system_time(time: Time) -> SystemTime {
+    let now_system = SystemTime::now();
+    let offset = now().as_nanos() - time.as_nanos();
+
+    if offset >= 0 {
+        now_system - std::time::Duration::from_nanos(offset as u64)
+    } else {
+        now_system + std::time::Duration::from_nanos(offset.unsigned_abs())
+    }
+}
@@ -0,0 +1,210 @@
+use openxr_sys::View;
+use quaternion_core::{self as quat, Quaternion, QuaternionOps, Vector3};
+
+use crate::{config::Config, pose, source::EyeGazeData};
+
+/// Where the gaze rays of both eyes converge.
+#[derive(Debug, Clone, Copy)]
+pub struct Fixation {
+    /// Metres, relative to the point between the eyes in the orientation of the views, with -Z
+    /// forward.
+    pub position: Vector3<f32>,
+    /// Metres from the point between the eyes.
+    pub distance: f32,
+    /// 0 to 1, 0 if the rays don't converge in front of the user.
+    pub confidence: f32,
+}
+
+/// Pose of each eye relative to the point between them, taken from the views returned by
+/// `xrLocateViews`.
+#[derive(Debug, Clone, Copy)]
+pub struct EyeFrames {
+    positions: [Vector3<f32>; 2],
+    orientations: [Quaternion<f32>; 2],
+}
+
+impl EyeFrames {
+    /// `views` must hold the left and the right view.
+    pub fn from_views(views: &[View]) -> EyeFrames {
+        let position = |v: &View| pose::from_vector3f(v.pose.position);
+        let orientation = |v: &View| pose::from_quaternionf(v.pose.orientation);
+
+        let center = position(&views[0]).add(position(&views[1])).scale(0.5);
+        let head = quat::slerp(orientation(&views[0]), orientation(&views[1]), 0.5);
+        let to_head = quat::conj(head);
+
+        EyeFrames {
+            positions: [&views[0], &views[1]]
+                .map(|v| quat::point_rotation(to_head, position(v).sub(center))),
+            orientations: [&views[0], &views[1]].map(|v| quat::mul(to_head, orientation(v))),
+        }
+    }
+}
+
+/// Triangulates the fixation point from the gaze of both eyes.
+pub struct Vergence {
+    /// Metres, fixations further away are clamped to this distance.
+    max_distance: f32,
+    /// Use the fixation point as the position of the eye gaze pose.
+    is_gaze_origin: bool,
+    /// Minimum confidence to use the fixation point as the gaze pose position.
+    min_confidence: f32,
+}
+
+impl Vergence {
+    pub fn new(config: &Config) -> Vergence {
+        let is_gaze_origin = match config.get_str("vergence.gaze_origin") {
+            Some("head") | None => false,
+            Some("fixation") => true,
+            Some(origin) => {
+                println!("Unknown gaze origin {origin}, using head");
+                false
+            }
+        };
+
+        Vergence {
+            max_distance: config.get("vergence.max_distance", 10.0),
+            is_gaze_origin,
+            min_confidence: config.get("vergence.min_confidence", 0.5),
+        }
+    }
+
+    /// The fixation point to use as the eye gaze pose position, if enabled and good enough.
+    pub fn gaze_origin(&self, fixation: Option<&Fixation>) -> Option<Vector3<f32>> {
+        fixation
+            .filter(|f| self.is_gaze_origin && f.confidence >= self.min_confidence)
+            .map(|f| f.position)
+    }
+
+    /// Triangulate the calibrated `sample` seen from the eyes in `frames`.
+    pub fn estimate(&self, frames: &EyeFrames, sample: &EyeGazeData) -> Fixation {
+        let direction =
+            |eye: usize, gaze: Vector3<f32>| quat::point_rotation(frames.orientations[eye], gaze);
+
+        let [p1, p2] = frames.positions;
+        let d1 = direction(0, sample.l_direction);
+        let d2 = direction(1, sample.r_direction);
+
+        let mean_direction = quat::normalize(d1.add(d2));
+        let unconverged = Fixation {
+            position: mean_direction.scale(self.max_distance),
+            distance: self.max_distance,
+            confidence: 0.0,
+        };
+
+        // Closest points between the two rays.
+        let w0 = p1.sub(p2);
+        let b = quat::dot(d1, d2);
+        let d = quat::dot(d1, w0);
+        let e = quat::dot(d2, w0);
+        let denominator = 1.0 - b * b;
+        if denominator < 1e-6 {
+            return unconverged;
+        }
+        let t1 = (b * e - d) / denominator;
+        let t2 = (e - b * d) / denominator;
+        if t1 <= 0.0 || t2 <= 0.0 {
+            return unconverged;
+        }
+
+        let q1 = p1.add(d1.scale(t1));
+        let q2 = p2.add(d2.scale(t2));
+        let position = q1.add(q2).scale(0.5);
+        let distance = quat::norm(position);
+        if distance > self.max_distance {
+            return unconverged;
+        }
+
+        // Vergence gets less precise with distance, and the rays should nearly intersect.
+        let ipd = quat::norm(w0).max(f32::EPSILON);
+        let gap = quat::norm(q1.sub(q2));
+        let tracker_confidence = match (sample.l_confidence, sample.r_confidence) {
+            (Some(l), Some(r)) => l.min(r),
+            (Some(c), None) | (None, Some(c)) => c,
+            (None, None) => 1.0,
+        };
+        let confidence = (1.0 - distance / self.max_distance)
+            * (1.0 - gap / ipd).clamp(0.0, 1.0)
+            * sample.l_openness.min(sample.r_openness)
+            * tracker_confidence;
+
+        Fixation {
+            position,
+            distance,
+            confidence: confidence.clamp(0.0, 1.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use openxr_sys::{Fovf, Posef, Quaternionf, Vector3f};
+
+    use super::*;
+
+    /// Half the distance between the eyes.
+    const HALF_IPD: f32 = 0.032;
+
+    fn view(x: f32) -> View {
+        View {
+            ty: View::TYPE,
+            next: std::ptr::null_mut(),
+            pose: Posef {
+                orientation: Quaternionf::IDENTITY,
+                position: Vector3f { x, y: 1.6, z: 0.0 },
+            },
+            fov: Fovf {
+                angle_left: 0.0,
+                angle_right: 0.0,
+                angle_up: 0.0,
+                angle_down: 0.0,
+            },
+        }
+    }
+
+    /// Both eyes looking at `target`, relative to the point between them.
+    fn estimate(target: Vector3<f32>) -> Fixation {
+        let frames = EyeFrames::from_views(&[view(-HALF_IPD), view(HALF_IPD)]);
+        let sample = EyeGazeData {
+            l_direction: pose::normalize_direction(target.sub([-HALF_IPD, 0.0, 0.0])),
+            r_direction: pose::normalize_direction(target.sub([HALF_IPD, 0.0, 0.0])),
+            ..Default::default()
+        };
+        Vergence::new(&Config::parse("")).estimate(&frames, &sample)
+    }
+
+    #[test]
+    fn converging_rays_meet_at_the_target() {
+        let target = [0.1, -0.05, -0.5];
+        let fixation = estimate(target);
+
+        let error = quat::norm(fixation.position.sub(target));
+        assert!(error < 1e-4, "{fixation:?}");
+        assert!(
+            (fixation.distance - quat::norm(target)).abs() < 1e-4,
+            "{fixation:?}"
+        );
+        // Only the distance out of the 10 m range lowers the confidence.
+        let expected_confidence = 1.0 - quat::norm(target) / 10.0;
+        assert!(
+            (fixation.confidence - expected_confidence).abs() < 1e-3,
+            "{fixation:?}"
+        );
+    }
+
+    #[test]
+    fn closer_fixations_are_more_confident() {
+        let near = estimate([0.0, 0.0, -0.5]);
+        let far = estimate([0.0, 0.0, -5.0]);
+        assert!((far.distance - 5.0).abs() < 0.05, "{far:?}");
+        assert!(near.confidence > far.confidence, "{near:?} {far:?}");
+    }
+
+    #[test]
+    fn parallel_rays_have_no_confidence() {
+        let frames = EyeFrames::from_views(&[view(-HALF_IPD), view(HALF_IPD)]);
+        let fixation = Vergence::new(&Config::parse("")).estimate(&frames, &EyeGazeData::default());
+        assert_eq!(fixation.distance, 10.0);
+        assert_eq!(fixation.confidence, 0.0);
+    }
+}